@@ -1,14 +1,22 @@
-use std::fmt;
-use std::sync::{Arc, Mutex};
-use slab::Slab;
-use futures::{future, Future};
+mod models;
+mod router;
+mod store;
+
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use futures::{future, Future, Stream};
 use hyper::{Body, Response, Server, Error, Method, Request, StatusCode};
+use hyper::header::{CONTENT_TYPE, LINK, HeaderValue};
 use hyper::service::service_fn;
-use lazy_static::lazy_static;
-use regex::Regex;
+use serde::Serialize;
+use serde_json::error::Category;
+use models::{ListQuery, UserData, UserEntry, UserForm, UserId};
+use router::{Params, ResponseFuture, Router};
+use store::{MemoryStore, Page, SqliteStore, StoreError, UserStore};
 
 
-const INDEX: &'static str = r#"
+const INDEX: &str = r#"
 <!doctype html>
 <html>
     <head>
@@ -20,109 +28,168 @@ const INDEX: &'static str = r#"
 </html>
 "#;
 
-lazy_static! {
-    static ref INDEX_PATH: Regex =
-        Regex::new("^/(index\\.html?)?$").unwrap();
-    static ref USER_PATH: Regex =
-        Regex::new("^/user/((?P<user_id>\\d+?)/?)?$").unwrap();
-    static ref USERS_PATH: Regex = 
-        Regex::new("^/users/?$").unwrap();
-}
-
-
-type UserId = u64;
-struct UserData;
-type UserDb = Arc<Mutex<Slab<UserData>>>; 
+const USERS: &str = "/users/?";
+const USER: &str = "/user/?";
+const USER_ID: &str = "/user/(?P<user_id>\\d+)/?";
 
 
+/// Users are kept in memory unless `USER_DB` names a SQLite file to keep them in.
 fn main() {
     let addr = ([127, 0, 0, 1], 8080).into();
+    match env::var("USER_DB") {
+        Ok(path) => {
+            let store = SqliteStore::open(&path)
+                .unwrap_or_else(|err| panic!("can't open user db {}: {}", path, err));
+            serve(addr, store)
+        },
+        Err(_) => serve(addr, MemoryStore::new()),
+    }
+}
+
+
+fn serve<S: UserStore>(addr: SocketAddr, store: S) {
     let builder = Server::bind(&addr);
-    let user_db = Arc::new(Mutex::new(Slab::new()));
+    let store = Arc::new(store);
+    let router = Arc::new(router::<S>());
     let server = builder.serve(move || {
-        let user_db = user_db.clone();
-        service_fn(move |req| microservice_handler(req, &user_db))
+        let store = store.clone();
+        let router = router.clone();
+        service_fn(move |req| router.handle(req, &store))
     });
     let server = server.map_err(drop);
     hyper::rt::run(server);
 }
 
 
-fn microservice_handler(req: Request<Body>, user_db: &UserDb) 
-    -> impl Future<Item=Response<Body>, Error=Error>
-{
-    let response = {
-        let method = req.method();
-        let path = req.uri().path();
-        let mut users = user_db.lock().unwrap();
-
-        if INDEX_PATH.is_match(path) {
-            if method == &Method::GET {
-                Response::new(INDEX.into())
-            } else {
-                response_with_code(StatusCode::METHOD_NOT_ALLOWED)
-            }
-        } else if USERS_PATH.is_match(path) {
-            if method == &Method::GET {
-                let list = users.iter()
-                    .map(|(id, _)| id.to_string())
-                    .collect::<Vec<String>>()
-                    .join(",");
-                Response::new(list.into())
-            } else {
-                response_with_code(StatusCode::METHOD_NOT_ALLOWED)
-            }
-        } else if let Some(cap) = USER_PATH.captures(path) {
-            let user_id = cap.name("user_id").and_then(|m| {
-                m.as_str()
-                    .parse::<UserId>()
-                    .ok()
-                    .map(|x| x as usize)
-            });
-            match (method, user_id) {
-                (&Method::POST, None) => {
-                    let id = users.insert(UserData);
-                    Response::new(id.to_string().into())
-                },
-                (&Method::POST, Some(_)) => {
-                    response_with_code(StatusCode::BAD_REQUEST)
-                },
-                (&Method::GET, Some(id)) => {
-                    if let Some(data) = users.get(id) {
-                        Response::new(data.to_string().into())
-                    } else {
-                        response_with_code(StatusCode::NOT_FOUND)
-                    }
-                },
-                (&Method::PUT, Some(id)) => {
-                    if let Some(user) = users.get_mut(id) {
-                        *user = UserData;
-                        response_with_code(StatusCode::OK)
-                    } else {
-                        response_with_code(StatusCode::NOT_FOUND)
-                    }
-                },
-                (&Method::DELETE, Some(id)) => {
-                    if users.contains(id) {
-                        users.remove(id);
-                        response_with_code(StatusCode::OK)
-                    } else {
-                        response_with_code(StatusCode::NOT_FOUND)
-                    }
-                },
-                _ => {
-                    response_with_code(StatusCode::METHOD_NOT_ALLOWED)
-                },
-            }
-        } else {
-            response_with_code(StatusCode::NOT_FOUND)
-        }
+/// Routes of the service. Add new endpoints here.
+fn router<S: UserStore>() -> Router<Arc<S>> {
+    Router::new()
+        .route(Method::GET, "/(index\\.html?)?", index)
+        .route(Method::GET, USERS, list_users::<S>)
+        .route(Method::POST, USER, create_user::<S>)
+        .route(Method::POST, USER_ID, |_, _, _| response_with_code(StatusCode::BAD_REQUEST))
+        .route(Method::GET, USER_ID, get_user::<S>)
+        .route(Method::PUT, USER_ID, update_user::<S>)
+        .route(Method::DELETE, USER_ID, delete_user::<S>)
+}
+
+
+fn index<T>(_: Request<Body>, _: Params, _: &T) -> ResponseFuture {
+    Box::new(future::ok(Response::new(INDEX.into())))
+}
+
+
+fn list_users<S: UserStore>(req: Request<Body>, _: Params, store: &Arc<S>) -> ResponseFuture {
+    let query = match ListQuery::parse(req.uri().query().unwrap_or("")) {
+        Ok(query) => query,
+        Err(msg) => {
+            return Box::new(future::ok(response_with_error(StatusCode::BAD_REQUEST, &msg)));
+        },
+    };
+    let resp = match store.list(&query) {
+        Ok(page) => response_with_page(req.uri().path(), &query, &page),
+        Err(err) => response_with_store_error(err),
+    };
+    Box::new(future::ok(resp))
+}
+
+
+fn create_user<S: UserStore>(req: Request<Body>, _: Params, store: &Arc<S>) -> ResponseFuture {
+    let store = store.clone();
+    let resp = read_form(req.into_body())
+        .map(move |form| match form {
+            Ok(form) => match store.insert(UserData::new(form)) {
+                Ok(id) => Response::new(id.to_string().into()),
+                Err(err) => response_with_store_error(err),
+            },
+            Err((status_code, msg)) => response_with_error(status_code, &msg),
+        });
+    Box::new(resp)
+}
+
+
+fn get_user<S: UserStore>(_: Request<Body>, params: Params, store: &Arc<S>) -> ResponseFuture {
+    let id = match user_id(&params) {
+        Some(id) => id,
+        None => return response_with_code(StatusCode::NOT_FOUND),
+    };
+    let resp = match store.get(id) {
+        Ok(Some(data)) => response_with_json(&data),
+        Ok(None) => empty_response(StatusCode::NOT_FOUND),
+        Err(err) => response_with_store_error(err),
+    };
+    Box::new(future::ok(resp))
+}
+
+
+fn update_user<S: UserStore>(req: Request<Body>, params: Params, store: &Arc<S>) -> ResponseFuture {
+    let id = match user_id(&params) {
+        Some(id) => id,
+        None => return response_with_code(StatusCode::NOT_FOUND),
+    };
+    let store = store.clone();
+    let resp = read_form(req.into_body())
+        .map(move |form| match form {
+            Ok(form) => match store.update(id, form) {
+                Ok(true) => empty_response(StatusCode::OK),
+                Ok(false) => empty_response(StatusCode::NOT_FOUND),
+                Err(err) => response_with_store_error(err),
+            },
+            Err((status_code, msg)) => response_with_error(status_code, &msg),
+        });
+    Box::new(resp)
+}
+
+
+fn delete_user<S: UserStore>(_: Request<Body>, params: Params, store: &Arc<S>) -> ResponseFuture {
+    let id = match user_id(&params) {
+        Some(id) => id,
+        None => return response_with_code(StatusCode::NOT_FOUND),
     };
-    future::ok(response)
+    let resp = match store.delete(id) {
+        Ok(true) => empty_response(StatusCode::OK),
+        Ok(false) => empty_response(StatusCode::NOT_FOUND),
+        Err(err) => response_with_store_error(err),
+    };
+    Box::new(future::ok(resp))
+}
+
+
+/// Ids too large for `UserId` can't belong to any user.
+fn user_id(params: &Params) -> Option<UserId> {
+    params.get("user_id").and_then(|id| id.parse().ok())
+}
+
+
+/// Collects the request body and parses it as a `UserForm`.
+///
+/// Malformed JSON is answered with `400 Bad Request`, well-formed JSON that
+/// doesn't describe a valid user with `422 Unprocessable Entity`.
+fn read_form(body: Body) -> impl Future<Item=Result<UserForm, (StatusCode, String)>, Error=Error> {
+    body.concat2().map(|chunk| parse_form(&chunk))
+}
+
+
+fn parse_form(data: &[u8]) -> Result<UserForm, (StatusCode, String)> {
+    let form = serde_json::from_slice::<UserForm>(data).map_err(|err| {
+        let status_code = match err.classify() {
+            Category::Data => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status_code, err.to_string())
+    })?;
+    form.validate()
+        .map_err(|msg| (StatusCode::UNPROCESSABLE_ENTITY, msg))?;
+    Ok(form)
+}
+
+
+fn response_with_code(status_code: StatusCode) -> ResponseFuture {
+    Box::new(future::ok(empty_response(status_code)))
 }
 
 
-fn response_with_code(status_code: StatusCode) -> Response<Body> {
+fn empty_response(status_code: StatusCode) -> Response<Body> {
     Response::builder()
         .status(status_code)
         .body(Body::empty())
@@ -130,8 +197,55 @@ fn response_with_code(status_code: StatusCode) -> Response<Body> {
 }
 
 
-impl fmt::Display for UserData {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str("{}")
+fn response_with_error(status_code: StatusCode, msg: &str) -> Response<Body> {
+    Response::builder()
+        .status(status_code)
+        .body(msg.to_owned().into())
+        .unwrap()
+}
+
+
+fn response_with_store_error(err: StoreError) -> Response<Body> {
+    response_with_error(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string())
+}
+
+
+/// Renders a page of users as a JSON array with `X-Total-Count` and
+/// `first`/`prev`/`next`/`last` links in the `Link` header.
+fn response_with_page(path: &str, query: &ListQuery, page: &Page) -> Response<Body> {
+    let entries = page.users.iter()
+        .map(|(id, user)| UserEntry { id: *id, user })
+        .collect::<Vec<_>>();
+    let mut resp = response_with_json(&entries);
+
+    let mut links = Vec::new();
+    let mut link = |offset: usize, rel: &str| {
+        links.push(format!("<{}?{}>; rel=\"{}\"", path, query.to_query_string(offset), rel));
+    };
+    let last = page.total.saturating_sub(1) / query.limit * query.limit;
+    link(0, "first");
+    if query.offset > 0 {
+        link(query.offset.saturating_sub(query.limit), "prev");
     }
-} 
+    let next = query.offset.saturating_add(query.limit);
+    if next < page.total {
+        link(next, "next");
+    }
+    link(last, "last");
+
+    let headers = resp.headers_mut();
+    headers.insert("x-total-count", HeaderValue::from(page.total));
+    if let Ok(value) = HeaderValue::from_str(&links.join(", ")) {
+        headers.insert(LINK, value);
+    }
+    resp
+}
+
+
+fn response_with_json<T: Serialize>(data: &T) -> Response<Body> {
+    let body = serde_json::to_vec(data).unwrap();
+    let mut resp = Response::new(body.into());
+    resp.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    resp
+}
@@ -0,0 +1,209 @@
+use serde_derive::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+
+pub type UserId = u64;
+
+
+#[derive(Serialize, Clone)]
+pub struct UserData {
+    pub name: String,
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+
+/// Item of the `GET /users` response.
+#[derive(Serialize)]
+pub struct UserEntry<'a> {
+    pub id: UserId,
+    #[serde(flatten)]
+    pub user: &'a UserData,
+}
+
+
+/// Fields a client is allowed to set with `POST /user/` and `PUT /user/{id}`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserForm {
+    pub name: String,
+    pub email: String,
+}
+
+
+impl UserForm {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        let mut parts = self.email.splitn(2, '@');
+        match (parts.next(), parts.next()) {
+            (Some(local), Some(domain)) if !local.is_empty() && !domain.is_empty() => Ok(()),
+            _ => Err(format!("invalid email {}", self.email)),
+        }
+    }
+}
+
+
+impl UserData {
+    pub fn new(form: UserForm) -> Self {
+        let now = Utc::now();
+        UserData {
+            name: form.name,
+            email: form.email,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn update(&mut self, form: UserForm) {
+        self.name = form.name;
+        self.email = form.email;
+        self.updated_at = Utc::now();
+    }
+}
+
+
+/// Default and maximal page size of `GET /users`.
+pub const DEFAULT_LIMIT: usize = 20;
+pub const MAX_LIMIT: usize = 100;
+/// The largest offset SQLite takes.
+pub const MAX_OFFSET: usize = i64::MAX as usize;
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SortField {
+    Id,
+    Name,
+    Email,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl SortField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortField::Id => "id",
+            SortField::Name => "name",
+            SortField::Email => "email",
+            SortField::CreatedAt => "created_at",
+            SortField::UpdatedAt => "updated_at",
+        }
+    }
+
+    fn parse(field: &str) -> Option<Self> {
+        match field {
+            "id" => Some(SortField::Id),
+            "name" => Some(SortField::Name),
+            "email" => Some(SortField::Email),
+            "created_at" => Some(SortField::CreatedAt),
+            "updated_at" => Some(SortField::UpdatedAt),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListParams {
+    offset: Option<usize>,
+    limit: Option<usize>,
+    sort: Option<String>,
+    name: Option<String>,
+    email: Option<String>,
+}
+
+
+/// Query of `GET /users?offset=&limit=&sort=&name=&email=`.
+///
+/// `sort` names a field, prefixed with `-` for descending order. `name` and
+/// `email` keep only users whose field contains the value, ignoring case.
+#[derive(Clone, Debug)]
+pub struct ListQuery {
+    pub offset: usize,
+    pub limit: usize,
+    pub sort: SortField,
+    pub descending: bool,
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl Default for ListQuery {
+    fn default() -> Self {
+        ListQuery {
+            offset: 0,
+            limit: DEFAULT_LIMIT,
+            sort: SortField::Id,
+            descending: false,
+            name: None,
+            email: None,
+        }
+    }
+}
+
+impl ListQuery {
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let params = serde_urlencoded::from_str::<ListParams>(query)
+            .map_err(|err| err.to_string())?;
+        let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 || limit > MAX_LIMIT {
+            return Err(format!("limit must be between 1 and {}", MAX_LIMIT));
+        }
+        let offset = params.offset.unwrap_or(0);
+        if offset > MAX_OFFSET {
+            return Err(format!("offset must be at most {}", MAX_OFFSET));
+        }
+        let (sort, descending) = match params.sort.as_deref() {
+            None => (SortField::Id, false),
+            Some(sort) => {
+                let (field, descending) = match sort.strip_prefix('-') {
+                    Some(field) => (field, true),
+                    None => (sort, false),
+                };
+                let field = SortField::parse(field)
+                    .ok_or_else(|| format!("can't sort by {}", field))?;
+                (field, descending)
+            },
+        };
+        Ok(ListQuery {
+            offset,
+            limit,
+            sort,
+            descending,
+            name: params.name,
+            email: params.email,
+        })
+    }
+
+    /// Renders the query back, pointing to another page.
+    pub fn to_query_string(&self, offset: usize) -> String {
+        let mut sort = String::new();
+        if self.descending {
+            sort.push('-');
+        }
+        sort.push_str(self.sort.as_str());
+        let mut params = vec![
+            ("offset", offset.to_string()),
+            ("limit", self.limit.to_string()),
+            ("sort", sort),
+        ];
+        if let Some(ref name) = self.name {
+            params.push(("name", name.clone()));
+        }
+        if let Some(ref email) = self.email {
+            params.push(("email", email.clone()));
+        }
+        serde_urlencoded::to_string(params).unwrap()
+    }
+
+    pub fn matches(&self, user: &UserData) -> bool {
+        fn contains(field: &str, pattern: &Option<String>) -> bool {
+            pattern.as_ref()
+                .map(|pattern| field.to_lowercase().contains(&pattern.to_lowercase()))
+                .unwrap_or(true)
+        }
+        contains(&user.name, &self.name) && contains(&user.email, &self.email)
+    }
+}
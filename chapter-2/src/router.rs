@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use futures::{future, Future};
+use hyper::{Body, Error, Method, Request, Response, StatusCode};
+use hyper::header::{ALLOW, HeaderValue};
+use regex::Regex;
+
+
+pub type ResponseFuture = Box<dyn Future<Item=Response<Body>, Error=Error> + Send>;
+
+type Handler<T> = Box<dyn Fn(Request<Body>, Params, &T) -> ResponseFuture + Send + Sync>;
+
+
+/// Named captures of the pattern that matched the request path.
+pub struct Params {
+    captures: HashMap<String, String>,
+}
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.captures.get(name).map(String::as_str)
+    }
+}
+
+
+struct Route<T> {
+    method: Method,
+    pattern: Regex,
+    handler: Handler<T>,
+}
+
+
+/// Table of `(Method, pattern) -> handler` entries.
+///
+/// Patterns are regular expressions matched against the whole path, their
+/// named groups are passed to the handler as `Params`. Every handler also
+/// gets a reference to the state `T` shared by all requests.
+pub struct Router<T> {
+    routes: Vec<Route<T>>,
+}
+
+impl<T> Default for Router<T> {
+    fn default() -> Self {
+        Router {
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl<T> Router<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a handler. Panics if `pattern` is not a valid regular expression.
+    pub fn route<F>(mut self, method: Method, pattern: &str, handler: F) -> Self
+    where
+        F: Fn(Request<Body>, Params, &T) -> ResponseFuture + Send + Sync + 'static,
+    {
+        let pattern = Regex::new(&format!("^(?:{})$", pattern))
+            .unwrap_or_else(|err| panic!("invalid route pattern {}: {}", pattern, err));
+        self.routes.push(Route {
+            method,
+            pattern,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Calls the first handler registered for the method and the path of `req`.
+    ///
+    /// Responds with `405 Method Not Allowed` and an `Allow` header when only
+    /// the method doesn't match any route, and with `404 Not Found` otherwise.
+    pub fn handle(&self, req: Request<Body>, state: &T) -> ResponseFuture {
+        let mut allowed: Vec<&Method> = Vec::new();
+        let mut found = None;
+        for route in &self.routes {
+            if let Some(cap) = route.pattern.captures(req.uri().path()) {
+                if route.method == *req.method() {
+                    let captures = route.pattern.capture_names()
+                        .flatten()
+                        .filter_map(|name| {
+                            cap.name(name).map(|m| (name.to_owned(), m.as_str().to_owned()))
+                        })
+                        .collect();
+                    found = Some((route, Params { captures }));
+                    break;
+                } else if !allowed.contains(&&route.method) {
+                    allowed.push(&route.method);
+                }
+            }
+        }
+        if let Some((route, params)) = found {
+            return (route.handler)(req, params, state);
+        }
+        let resp = if allowed.is_empty() {
+            Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .unwrap()
+        } else {
+            let allow = allowed.iter()
+                .map(|method| method.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .header(ALLOW, HeaderValue::from_str(&allow).unwrap())
+                .body(Body::empty())
+                .unwrap()
+        };
+        Box::new(future::ok(resp))
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::Mutex;
+use slab::Slab;
+use crate::models::{ListQuery, SortField, UserData, UserForm, UserId};
+use super::{Page, StoreError, UserStore};
+
+
+/// Keeps users in a `Slab`; everything is lost when the process exits.
+#[derive(Default)]
+pub struct MemoryStore {
+    users: Mutex<Slab<UserData>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl UserStore for MemoryStore {
+    fn insert(&self, user: UserData) -> Result<UserId, StoreError> {
+        let id = self.users.lock().unwrap().insert(user);
+        Ok(id as UserId)
+    }
+
+    fn get(&self, id: UserId) -> Result<Option<UserData>, StoreError> {
+        let users = self.users.lock().unwrap();
+        Ok(users.get(id as usize).cloned())
+    }
+
+    fn update(&self, id: UserId, form: UserForm) -> Result<bool, StoreError> {
+        let mut users = self.users.lock().unwrap();
+        match users.get_mut(id as usize) {
+            Some(user) => {
+                user.update(form);
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    fn delete(&self, id: UserId) -> Result<bool, StoreError> {
+        let mut users = self.users.lock().unwrap();
+        Ok(users.try_remove(id as usize).is_some())
+    }
+
+    fn list(&self, query: &ListQuery) -> Result<Page, StoreError> {
+        let mut list = {
+            let users = self.users.lock().unwrap();
+            users.iter()
+                .filter(|(_, user)| query.matches(user))
+                .map(|(id, user)| (id as UserId, user.clone()))
+                .collect::<Vec<_>>()
+        };
+        list.sort_by(|(a_id, a), (b_id, b)| {
+            let ord = match query.sort {
+                SortField::Id => a_id.cmp(b_id),
+                SortField::Name => a.name.cmp(&b.name),
+                SortField::Email => a.email.cmp(&b.email),
+                SortField::CreatedAt => a.created_at.cmp(&b.created_at),
+                SortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+            };
+            let ord = if query.descending { ord.reverse() } else { ord };
+            ord.then(a_id.cmp(b_id))
+        });
+        let total = list.len();
+        let users = list.into_iter()
+            .skip(query.offset)
+            .take(query.limit)
+            .collect();
+        Ok(Page { total, users })
+    }
+}
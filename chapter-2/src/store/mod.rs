@@ -0,0 +1,52 @@
+mod memory;
+mod sqlite;
+
+use std::fmt;
+use crate::models::{ListQuery, UserData, UserForm, UserId};
+
+pub use memory::MemoryStore;
+pub use sqlite::SqliteStore;
+
+
+/// Storage backend of the user service.
+///
+/// Every method takes `&self`, so an implementation is responsible for its
+/// own locking and a request never has to hold a lock on the whole store.
+pub trait UserStore: Send + Sync + 'static {
+    fn insert(&self, user: UserData) -> Result<UserId, StoreError>;
+    fn get(&self, id: UserId) -> Result<Option<UserData>, StoreError>;
+    /// Returns `false` if there is no user with `id`.
+    fn update(&self, id: UserId, form: UserForm) -> Result<bool, StoreError>;
+    /// Returns `false` if there is no user with `id`.
+    fn delete(&self, id: UserId) -> Result<bool, StoreError>;
+    /// Returns the users matching `query`, sorted and cut to the requested page.
+    fn list(&self, query: &ListQuery) -> Result<Page, StoreError>;
+}
+
+
+/// One page of `UserStore::list`.
+pub struct Page {
+    /// Number of users matching the query, regardless of the page bounds.
+    pub total: usize,
+    pub users: Vec<(UserId, UserData)>,
+}
+
+
+#[derive(Debug)]
+pub enum StoreError {
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Sqlite(err) => write!(f, "sqlite error: {}", err),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        StoreError::Sqlite(err)
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::Path;
+use std::sync::Mutex;
+use chrono::Utc;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row, ToSql};
+use crate::models::{ListQuery, UserData, UserForm, UserId};
+use super::{Page, StoreError, UserStore};
+
+
+const CREATE_USERS: &str = r#"
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    email TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+)
+"#;
+
+
+/// Keeps users in a SQLite file, so they survive a restart.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Opens (or creates) the database file and makes sure the `users` table exists.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
+        let conn = Connection::open(path)?;
+        conn.execute(CREATE_USERS, [])?;
+        Ok(SqliteStore {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+fn user_from_row(row: &Row) -> rusqlite::Result<UserData> {
+    Ok(UserData {
+        name: row.get("name")?,
+        email: row.get("email")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+    })
+}
+
+impl UserStore for SqliteStore {
+    fn insert(&self, user: UserData) -> Result<UserId, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO users (name, email, created_at, updated_at) VALUES (?1, ?2, ?3, ?4)",
+            params![user.name, user.email, user.created_at, user.updated_at],
+        )?;
+        Ok(conn.last_insert_rowid() as UserId)
+    }
+
+    fn get(&self, id: UserId) -> Result<Option<UserData>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let user = conn.query_row(
+            "SELECT name, email, created_at, updated_at FROM users WHERE id = ?1",
+            params![id as i64],
+            user_from_row,
+        ).optional()?;
+        Ok(user)
+    }
+
+    fn update(&self, id: UserId, form: UserForm) -> Result<bool, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE users SET name = ?1, email = ?2, updated_at = ?3 WHERE id = ?4",
+            params![form.name, form.email, Utc::now(), id as i64],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn delete(&self, id: UserId) -> Result<bool, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM users WHERE id = ?1", params![id as i64])?;
+        Ok(deleted > 0)
+    }
+
+    fn list(&self, query: &ListQuery) -> Result<Page, StoreError> {
+        let mut filters = Vec::new();
+        let mut args = Vec::new();
+        for (column, pattern) in [("name", &query.name), ("email", &query.email)] {
+            if let Some(pattern) = pattern {
+                filters.push(format!("instr(lower({}), lower(?)) > 0", column));
+                args.push(pattern.clone());
+            }
+        }
+        let filter = if filters.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", filters.join(" AND "))
+        };
+        let order = if query.descending { "DESC" } else { "ASC" };
+
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM users {}", filter),
+            params_from_iter(args.iter()),
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, name, email, created_at, updated_at FROM users {} \
+             ORDER BY {} {}, id LIMIT ? OFFSET ?",
+            filter, query.sort.as_str(), order,
+        ))?;
+        let mut args: Vec<&dyn ToSql> = args.iter().map(|arg| arg as &dyn ToSql).collect();
+        let (limit, offset) = (query.limit as i64, query.offset as i64);
+        args.push(&limit);
+        args.push(&offset);
+        let rows = stmt.query_map(params_from_iter(args), |row| {
+            let id: i64 = row.get("id")?;
+            Ok((id as UserId, user_from_row(row)?))
+        })?;
+        let users = rows.collect::<Result<Vec<_>, _>>()?;
+        Ok(Page { total: total as usize, users })
+    }
+}
@@ -0,0 +1,179 @@
+use clap::ArgMatches;
+use log::LevelFilter;
+use serde_derive::Deserialize;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+
+const DEFAULT_CONFIG: &str = "microservice.toml";
+
+
+/// How a generated value is written to the response body.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Text,
+    Hex,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "hex" => Ok(Format::Hex),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format {}, expected text, hex or json", s)),
+        }
+    }
+}
+
+
+/// Settings of the service after all sources are merged and validated.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub address: SocketAddr,
+    /// The most detailed level logged, `RUST_LOG` still filters by module.
+    pub log_level: LevelFilter,
+    pub workers: usize,
+    pub format: Format,
+}
+
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(String, String),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "can't read {}: {}", path.display(), err),
+            ConfigError::Parse(source, err) => write!(f, "can't parse {}: {}", source, err),
+            ConfigError::Invalid(err) => write!(f, "invalid config: {}", err),
+        }
+    }
+}
+
+
+/// Values set by a single source, any of them may be missing.
+#[derive(Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Layer {
+    address: Option<SocketAddr>,
+    log_level: Option<String>,
+    workers: Option<usize>,
+    format: Option<Format>,
+}
+
+impl Layer {
+    fn from_file(path: &PathBuf, required: bool) -> Result<Self, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(buffer) => {
+                toml::from_str(&buffer)
+                    .map_err(|err| ConfigError::Parse(path.display().to_string(), err.to_string()))
+            },
+            Err(ref err) if !required && err.kind() == io::ErrorKind::NotFound => {
+                Ok(Layer::default())
+            },
+            Err(err) => Err(ConfigError::Io(path.clone(), err)),
+        }
+    }
+
+    fn from_env() -> Result<Self, ConfigError> {
+        Ok(Layer {
+            address: parse_value("ADDRESS", env::var("ADDRESS").ok())?,
+            log_level: env::var("LOG_LEVEL").ok(),
+            workers: parse_value("WORKERS", env::var("WORKERS").ok())?,
+            format: parse_value("FORMAT", env::var("FORMAT").ok())?,
+        })
+    }
+
+    fn from_args(matches: &ArgMatches) -> Result<Self, ConfigError> {
+        Ok(Layer {
+            address: parse_value("--address", matches.value_of("address"))?,
+            log_level: matches.value_of("log-level").map(String::from),
+            workers: parse_value("--workers", matches.value_of("workers"))?,
+            format: parse_value("--format", matches.value_of("format"))?,
+        })
+    }
+
+    /// Values of `over` win over the values of `self`.
+    fn merge(self, over: Layer) -> Self {
+        Layer {
+            address: over.address.or(self.address),
+            log_level: over.log_level.or(self.log_level),
+            workers: over.workers.or(self.workers),
+            format: over.format.or(self.format),
+        }
+    }
+}
+
+fn parse_value<T, S>(source: &str, value: Option<S>) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+    S: AsRef<str>,
+{
+    value
+        .map(|value| value.as_ref().parse::<T>())
+        .transpose()
+        .map_err(|err| ConfigError::Parse(source.to_owned(), err.to_string()))
+}
+
+
+/// Remembers where the config comes from, so it can be loaded again on reload.
+#[derive(Clone)]
+pub struct ConfigLoader {
+    path: PathBuf,
+    required: bool,
+    args: Layer,
+}
+
+impl ConfigLoader {
+    pub fn new(matches: &ArgMatches) -> Result<Self, ConfigError> {
+        let (path, required) = match matches.value_of("config") {
+            Some(path) => (PathBuf::from(path), true),
+            None => (PathBuf::from(DEFAULT_CONFIG), false),
+        };
+        Ok(ConfigLoader {
+            path,
+            required,
+            args: Layer::from_args(matches)?,
+        })
+    }
+
+    /// Merges the sources, each one overriding the ones before it:
+    ///
+    /// 1. built-in defaults;
+    /// 2. the TOML file given with `--config`, or `microservice.toml` if it exists;
+    /// 3. `ADDRESS`, `LOG_LEVEL`, `WORKERS` and `FORMAT` environment variables;
+    /// 4. command line arguments.
+    pub fn load(&self) -> Result<Config, ConfigError> {
+        let layer = Layer::from_file(&self.path, self.required)?
+            .merge(Layer::from_env()?)
+            .merge(self.args.clone());
+        let log_level = layer.log_level.as_deref().unwrap_or("info");
+        let log_level = LevelFilter::from_str(log_level)
+            .map_err(|_| ConfigError::Invalid(format!("unknown log level {}", log_level)))?;
+        let workers = layer.workers.unwrap_or(4);
+        if workers == 0 {
+            return Err(ConfigError::Invalid("workers must be greater than 0".into()));
+        }
+        Ok(Config {
+            address: layer.address.unwrap_or_else(|| ([127, 0, 0, 1], 8080).into()),
+            log_level,
+            workers,
+            format: layer.format.unwrap_or(Format::Text),
+        })
+    }
+}
@@ -1,40 +1,25 @@
-use hyper::{Body, Response, Server};
+mod config;
+
+use hyper::{Body, Request, Response, Server, StatusCode};
 use hyper::rt::Future;
 use hyper::service::service_fn_ok;
-use log::{debug, info, trace, warn};
+use log::{debug, error, info, trace, warn, LevelFilter};
 use pretty_env_logger as logger;
-use serde_derive::Deserialize;
-use std::env;
-use std::io::{self, Read};
-use std::fs::File;
-use std::net::SocketAddr;
+use rand::{Rng, SeedableRng};
+use rand::prng::ChaChaRng;
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
+use std::{env, process};
+use std::sync::{Arc, RwLock};
+use std::thread;
 use dotenv::dotenv;
 use clap::{crate_authors, crate_description, crate_name, crate_version,
 Arg, App};
-
-
-#[derive(Deserialize)]
-struct Config {
-    address: SocketAddr,
-}
+use config::{Config, ConfigLoader, Format};
 
 
 fn main() {
     dotenv().ok();
-    let config = File::open("microservice.toml")
-        .and_then(|mut file| {
-            let mut buffer = String::new();
-            file.read_to_string(&mut buffer)?;
-            Ok(buffer)
-        })
-        .and_then(|buffer| {
-            toml::from_str::<Config>(&buffer)
-                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
-        })
-        .map_err(|err| {
-            warn!("Can't read config file: {}", err);
-        })
-        .ok();
     let matches = App::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!())
@@ -51,30 +36,149 @@ fn main() {
              .value_name("FILE")
              .help("Sets a custom config file")
              .takes_value(true))
+        .arg(Arg::with_name("log-level")
+             .short("l")
+             .long("log-level")
+             .value_name("LEVEL")
+             .help("Sets a log level: off, error, warn, info, debug or trace")
+             .takes_value(true))
+        .arg(Arg::with_name("workers")
+             .short("w")
+             .long("workers")
+             .value_name("COUNT")
+             .help("Sets a number of worker threads")
+             .takes_value(true))
+        .arg(Arg::with_name("format")
+             .short("f")
+             .long("format")
+             .value_name("FORMAT")
+             .help("Sets a response format: text, hex or json")
+             .takes_value(true))
         .get_matches();
-    logger::init();
+    let loaded = ConfigLoader::new(&matches)
+        .and_then(|loader| loader.load().map(|config| (loader, config)));
+    let (loader, config) = match loaded {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("Can't load config: {}", err);
+            process::exit(1);
+        },
+    };
+    // `RUST_LOG` picks what is logged, the configured level caps it
+    let mut builder = logger::formatted_builder();
+    match env::var("RUST_LOG") {
+        Ok(filters) => {
+            builder.parse_filters(&filters);
+        },
+        Err(_) => {
+            builder.filter_level(LevelFilter::Warn)
+                .filter_module(module_path!(), LevelFilter::Trace);
+        },
+    }
+    builder.init();
+    log::set_max_level(config.log_level);
     info!("Rand Microservice - v0.1.0");
     trace!("Starting...");
-    let addr = matches.value_of("address")
-        .map(|s| s.to_owned())
-        .or(env::var("ADDRESS").ok())
-        .and_then(|addr| addr.parse().ok())
-        .or(config.map(|config| config.address))
-        .or_else(|| Some(([127, 0, 0, 1], 8080).into()))
-        .unwrap();
+    let addr = config.address;
+    let workers = config.workers;
+    let config = Arc::new(RwLock::new(config));
+    watch_reload(loader, config.clone());
     debug!("Trying to bind server to address: {}", addr);
     let builder = Server::bind(&addr);
     trace!("Creating service handler...");
-    let server = builder.serve(|| {
-        service_fn_ok(|req| {
+    let server = builder.serve(move || {
+        let config = config.clone();
+        service_fn_ok(move |req| {
             trace!("Incoming request is: {:?}", req);
-            let random_byte = rand::random::<u8>();
-            debug!("Generated value is: {}", random_byte);
-            Response::new(Body::from(random_byte.to_string()))
+            let format = config.read().unwrap().format;
+            handle_request(&req, format)
         })
     });
     info!("Used address: {}", server.local_addr());
     let server = server.map_err(drop);
-    debug!("Run!");
-    hyper::rt::run(server);
+    debug!("Run with {} workers!", workers);
+    let runtime = tokio::runtime::Builder::new()
+        .core_threads(workers)
+        .build()
+        .unwrap_or_else(|err| {
+            error!("Can't start runtime: {}", err);
+            process::exit(1);
+        });
+    runtime.block_on_all(server).ok();
+}
+
+
+/// Generates a byte from a ChaCha stream seeded with the `seed` query
+/// parameter, or with a random seed if there is none. The seed is echoed in
+/// the `X-Seed` header (and in the body for JSON), so any value can be
+/// reproduced by sending the same seed again.
+fn handle_request(req: &Request<Body>, format: Format) -> Response<Body> {
+    let seed = match seed_param(req.uri().query().unwrap_or("")) {
+        Ok(seed) => seed.unwrap_or_else(rand::random),
+        Err(err) => {
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from(err))
+                .unwrap();
+        },
+    };
+    let mut rng = ChaChaRng::seed_from_u64(seed);
+    let random_byte = rng.gen::<u8>();
+    debug!("Generated value with seed {} is: {}", seed, random_byte);
+    let body = match format {
+        Format::Text => random_byte.to_string(),
+        Format::Hex => format!("{:02x}", random_byte),
+        Format::Json => format!("{{\"seed\":{},\"value\":{}}}", seed, random_byte),
+    };
+    Response::builder()
+        .header("X-Seed", seed.to_string().as_str())
+        .body(Body::from(body))
+        .unwrap()
+}
+
+
+fn seed_param(query: &str) -> Result<Option<u64>, String> {
+    query.split('&')
+        .filter_map(|pair| pair.strip_prefix("seed="))
+        .next()
+        .map(|seed| seed.parse::<u64>().map_err(|err| format!("invalid seed {}: {}", seed, err)))
+        .transpose()
+}
+
+
+/// Reloads the config on `SIGHUP`.
+///
+/// The address and the number of workers are fixed at startup, changes to
+/// them are only reported. If the new config is invalid the current one stays.
+fn watch_reload(loader: ConfigLoader, config: Arc<RwLock<Config>>) {
+    let mut signals = match Signals::new([SIGHUP]) {
+        Ok(signals) => signals,
+        Err(err) => {
+            warn!("Can't watch SIGHUP, config reload is disabled: {}", err);
+            return;
+        },
+    };
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            info!("SIGHUP received, reloading config");
+            let new = match loader.load() {
+                Ok(new) => new,
+                Err(err) => {
+                    warn!("Can't reload config, keeping the current one: {}", err);
+                    continue;
+                },
+            };
+            let mut current = config.write().unwrap();
+            if new.address != current.address {
+                warn!("Address change to {} requires a restart", new.address);
+            }
+            if new.workers != current.workers {
+                warn!("Workers change to {} requires a restart", new.workers);
+            }
+            current.log_level = new.log_level;
+            current.format = new.format;
+            log::set_max_level(current.log_level);
+            debug!("Config reloaded: {:?}", *current);
+        }
+    });
 }
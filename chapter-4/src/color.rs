@@ -5,8 +5,9 @@ use std::num::ParseIntError;
 use serde::{de::{self, Visitor}, Deserialize, Deserializer, Serialize, Serializer};
 
 
-pub const WHITE: Color = Color { red: 0xFF, green: 0xFF, blue: 0xFF };
-pub const BLACK: Color = Color { red: 0x00, green: 0x00, blue: 0x00 };
+pub const WHITE: Color = Color { red: 0xFF, green: 0xFF, blue: 0xFF, alpha: 0xFF };
+pub const BLACK: Color = Color { red: 0x00, green: 0x00, blue: 0x00, alpha: 0xFF };
+pub const TRANSPARENT: Color = Color { red: 0x00, green: 0x00, blue: 0x00, alpha: 0x00 };
 
 
 #[derive(Clone, PartialEq, Eq)]
@@ -14,6 +15,201 @@ pub struct Color {
     pub red: u8,
     pub green: u8,
     pub blue: u8,
+    pub alpha: u8,
+}
+
+
+/// Color space in which `Color::interpolate` mixes two colors.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSpace {
+    Rgb,
+    Hsl,
+    Lab,
+}
+
+
+/// Notation a color is written in by `Color::to_notation`.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Notation {
+    /// `#RRGGBB`, or `#RRGGBBAA` for translucent colors.
+    Hex,
+    /// `rgb(r, g, b)` or `rgba(r, g, b, a)`.
+    Rgb,
+    /// `hsl(h, s%, l%)` or `hsla(h, s%, l%, a)`.
+    Hsl,
+    /// CSS color name, or `Hex` if the color has no name.
+    Name,
+}
+
+
+impl Color {
+    pub fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Color { red, green, blue, alpha: 0xFF }
+    }
+
+    pub fn to_notation(&self, notation: Notation) -> String {
+        match notation {
+            Notation::Hex => self.to_hex(),
+            Notation::Rgb if self.alpha == 0xFF => {
+                format!("rgb({}, {}, {})", self.red, self.green, self.blue)
+            },
+            Notation::Rgb => {
+                format!("rgba({}, {}, {}, {})", self.red, self.green, self.blue, self.alpha_f64())
+            },
+            Notation::Hsl => {
+                let (h, s, l) = self.to_hsl();
+                let (h, s, l) = (h.round(), (s * 100.0).round(), (l * 100.0).round());
+                if self.alpha == 0xFF {
+                    format!("hsl({}, {}%, {}%)", h, s, l)
+                } else {
+                    format!("hsla({}, {}%, {}%, {})", h, s, l, self.alpha_f64())
+                }
+            },
+            Notation::Name => self.name().map(String::from).unwrap_or_else(|| self.to_hex()),
+        }
+    }
+
+    fn to_hex(&self) -> String {
+        if self.alpha == 0xFF {
+            format!("#{:02X}{:02X}{:02X}", self.red, self.green, self.blue)
+        } else {
+            format!("#{:02X}{:02X}{:02X}{:02X}", self.red, self.green, self.blue, self.alpha)
+        }
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        if *self == TRANSPARENT {
+            return Some("transparent");
+        }
+        if self.alpha != 0xFF {
+            return None;
+        }
+        let rgb = (self.red as u32) << 16 | (self.green as u32) << 8 | self.blue as u32;
+        NAMED_COLORS.iter()
+            .find(|(_, value)| *value == rgb)
+            .map(|(name, _)| *name)
+    }
+
+    fn alpha_f64(&self) -> f64 {
+        (self.alpha as f64 / 255.0 * 1000.0).round() / 1000.0
+    }
+
+    /// Mixes `self` and `other` in `space`, `t = 0` gives `self` and `t = 1` gives `other`.
+    ///
+    /// Hues are interpolated along the shorter arc of the color wheel.
+    pub fn interpolate(&self, other: &Color, t: f64, space: ColorSpace) -> Color {
+        let alpha = to_channel(lerp(self.alpha as f64, other.alpha as f64, t) / 255.0);
+        let mut color = match space {
+            ColorSpace::Rgb => {
+                let channel = |a: u8, b: u8| to_channel(lerp(a as f64, b as f64, t) / 255.0);
+                Color::rgb(channel(self.red, other.red),
+                           channel(self.green, other.green),
+                           channel(self.blue, other.blue))
+            },
+            ColorSpace::Hsl => {
+                let (h1, s1, l1) = self.to_hsl();
+                let (h2, s2, l2) = other.to_hsl();
+                let mut dh = h2 - h1;
+                if dh > 180.0 {
+                    dh -= 360.0;
+                } else if dh < -180.0 {
+                    dh += 360.0;
+                }
+                Color::from_hsl((h1 + dh * t).rem_euclid(360.0), lerp(s1, s2, t), lerp(l1, l2, t))
+            },
+            ColorSpace::Lab => {
+                let (l1, a1, b1) = self.to_lab();
+                let (l2, a2, b2) = other.to_lab();
+                Color::from_lab(lerp(l1, l2, t), lerp(a1, a2, t), lerp(b1, b2, t))
+            },
+        };
+        color.alpha = alpha;
+        color
+    }
+
+    /// Hue in degrees, saturation and lightness in `0..=1`.
+    pub fn to_hsl(&self) -> (f64, f64, f64) {
+        let (r, g, b) = (self.red as f64 / 255.0, self.green as f64 / 255.0, self.blue as f64 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let l = (max + min) / 2.0;
+        let d = max - min;
+        if d == 0.0 {
+            return (0.0, 0.0, l);
+        }
+        let s = d / (1.0 - (2.0 * l - 1.0).abs());
+        let h = if max == r {
+            ((g - b) / d).rem_euclid(6.0)
+        } else if max == g {
+            (b - r) / d + 2.0
+        } else {
+            (r - g) / d + 4.0
+        };
+        (h * 60.0, s, l)
+    }
+
+    pub fn from_hsl(h: f64, s: f64, l: f64) -> Color {
+        let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let h = h.rem_euclid(360.0) / 60.0;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = l - c / 2.0;
+        Color::rgb(to_channel(r + m), to_channel(g + m), to_channel(b + m))
+    }
+
+    /// CIE L*a*b* coordinates, assuming sRGB with the D65 white point.
+    pub fn to_lab(&self) -> (f64, f64, f64) {
+        fn linear(c: u8) -> f64 {
+            let c = c as f64 / 255.0;
+            if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        }
+        fn f(t: f64) -> f64 {
+            if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 }
+        }
+        let (r, g, b) = (linear(self.red), linear(self.green), linear(self.blue));
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+        (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+    }
+
+    pub fn from_lab(l: f64, a: f64, b: f64) -> Color {
+        fn f_inv(t: f64) -> f64 {
+            if t.powi(3) > 0.008856 { t.powi(3) } else { (t - 16.0 / 116.0) / 7.787 }
+        }
+        fn gamma(c: f64) -> u8 {
+            let c = if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+            to_channel(c)
+        }
+        let fy = (l + 16.0) / 116.0;
+        let x = f_inv(fy + a / 500.0) * 0.95047;
+        let y = f_inv(fy);
+        let z = f_inv(fy - b / 200.0) * 1.08883;
+        Color::rgb(gamma(3.2406 * x - 1.5372 * y - 0.4986 * z),
+                   gamma(-0.9689 * x + 1.8758 * y + 0.0415 * z),
+                   gamma(0.0557 * x - 0.2040 * y + 1.0570 * z))
+    }
+}
+
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+
+/// Converts `0..=1` to a channel value, clamping anything out of range.
+fn to_channel(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
 }
 
 
@@ -22,9 +218,7 @@ impl Display for Color {
         match self {
             &WHITE => f.write_str("white"),
             &BLACK => f.write_str("black"),
-            color => {
-                write!(f, "#{:02X}{:02X}{:02X}", color.red, color.green, color.blue)
-            },
+            color => f.write_str(&color.to_hex()),
         }
     }
 }
@@ -43,21 +237,125 @@ impl Serialize for Color {
 impl FromStr for Color {
     type Err = ColorError;
 
+    /// Parses `#RGB`, `#RGBA`, `#RRGGBB`, `#RRGGBBAA`, CSS color names and
+    /// the `rgb()`, `rgba()`, `hsl()` and `hsla()` functional notations.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "white" => Ok(WHITE.to_owned()),
-            "black" => Ok(BLACK.to_owned()),
-            s if s.starts_with("#") && s.len() == 7 => {
-                let red = u8::from_str_radix(&s[1..3], 16)?;
-                let green = u8::from_str_radix(&s[3..5], 16)?;
-                let blue = u8::from_str_radix(&s[5..7], 16)?;
-                Ok(Color { red, green, blue })
-            },
-            other => {
-                Err(ColorError::InvalidValue { value: other.to_owned()})
-            },
+        let s = s.trim();
+        let lower = s.to_lowercase();
+        if let Some(hex) = s.strip_prefix('#') {
+            return parse_hex(hex, s);
+        }
+        if let Some(args) = function_args(&lower, &["rgb", "rgba"]) {
+            return parse_rgb(&args).ok_or_else(|| invalid(s));
         }
+        if let Some(args) = function_args(&lower, &["hsl", "hsla"]) {
+            return parse_hsl(&args).ok_or_else(|| invalid(s));
+        }
+        if lower == "transparent" {
+            return Ok(TRANSPARENT);
+        }
+        NAMED_COLORS.iter()
+            .find(|(name, _)| *name == lower)
+            .map(|(_, rgb)| Color::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8))
+            .ok_or_else(|| invalid(s))
+    }
+}
+
+
+fn invalid(value: &str) -> ColorError {
+    ColorError::InvalidValue { value: value.to_owned() }
+}
+
+
+fn parse_hex(hex: &str, value: &str) -> Result<Color, ColorError> {
+    if !hex.is_ascii() {
+        return Err(invalid(value));
+    }
+    let digits = match hex.len() {
+        3 | 4 => hex.chars()
+            .map(|c| u8::from_str_radix(&c.to_string(), 16).map(|d| d * 0x11))
+            .collect::<Result<Vec<_>, _>>()?,
+        6 | 8 => (0..hex.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => return Err(invalid(value)),
+    };
+    Ok(Color {
+        red: digits[0],
+        green: digits[1],
+        blue: digits[2],
+        alpha: digits.get(3).cloned().unwrap_or(0xFF),
+    })
+}
+
+
+/// Arguments of `name(a, b, c)` or `name(a b c / d)` if `s` calls one of `names`.
+fn function_args(s: &str, names: &[&str]) -> Option<Vec<String>> {
+    let open = s.find('(')?;
+    if !names.contains(&s[..open].trim()) || !s.ends_with(')') {
+        return None;
     }
+    let args = s[open + 1..s.len() - 1]
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .map(String::from)
+        .collect();
+    Some(args)
+}
+
+
+/// Parses a number or a percentage of `scale`.
+fn parse_number(arg: &str, scale: f64) -> Option<f64> {
+    let value = match arg.strip_suffix('%') {
+        Some(percent) => percent.parse::<f64>().ok()? / 100.0 * scale,
+        None => arg.parse::<f64>().ok()?,
+    };
+    if value.is_finite() { Some(value) } else { None }
+}
+
+
+fn parse_alpha(args: &[String]) -> Option<u8> {
+    match args.get(3) {
+        Some(alpha) => parse_number(alpha, 1.0).map(to_channel),
+        None => Some(0xFF),
+    }
+}
+
+
+fn parse_rgb(args: &[String]) -> Option<Color> {
+    if args.len() != 3 && args.len() != 4 {
+        return None;
+    }
+    let channel = |arg: &String| parse_number(arg, 255.0).map(|v| to_channel(v / 255.0));
+    Some(Color {
+        red: channel(&args[0])?,
+        green: channel(&args[1])?,
+        blue: channel(&args[2])?,
+        alpha: parse_alpha(args)?,
+    })
+}
+
+
+fn parse_hsl(args: &[String]) -> Option<Color> {
+    if args.len() != 3 && args.len() != 4 {
+        return None;
+    }
+    let hue = &args[0];
+    let h = if let Some(turn) = hue.strip_suffix("turn") {
+        turn.parse::<f64>().ok()? * 360.0
+    } else if let Some(rad) = hue.strip_suffix("rad") {
+        rad.parse::<f64>().ok()?.to_degrees()
+    } else {
+        hue.trim_end_matches("deg").parse::<f64>().ok()?
+    };
+    let s = parse_number(&args[1], 1.0)?;
+    let l = parse_number(&args[2], 1.0)?;
+    if !h.is_finite() {
+        return None;
+    }
+    let mut color = Color::from_hsl(h, s.clamp(0.0, 1.0), l.clamp(0.0, 1.0));
+    color.alpha = parse_alpha(args)?;
+    Some(color)
 }
 
 
@@ -112,3 +410,58 @@ impl From<ParseIntError> for ColorError {
         ColorError::InvalidComponent(err)
     }
 }
+
+
+/// CSS Color Module Level 4 named colors.
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xF0F8FF), ("antiquewhite", 0xFAEBD7), ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4), ("azure", 0xF0FFFF), ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4), ("black", 0x000000), ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF), ("blueviolet", 0x8A2BE2), ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887), ("cadetblue", 0x5F9EA0), ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E), ("coral", 0xFF7F50), ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC), ("crimson", 0xDC143C), ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B), ("darkcyan", 0x008B8B), ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9), ("darkgreen", 0x006400), ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B), ("darkmagenta", 0x8B008B), ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00), ("darkorchid", 0x9932CC), ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A), ("darkseagreen", 0x8FBC8F), ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F), ("darkslategrey", 0x2F4F4F), ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3), ("deeppink", 0xFF1493), ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969), ("dimgrey", 0x696969), ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222), ("floralwhite", 0xFFFAF0), ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF), ("gainsboro", 0xDCDCDC), ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700), ("goldenrod", 0xDAA520), ("gray", 0x808080),
+    ("green", 0x008000), ("greenyellow", 0xADFF2F), ("grey", 0x808080),
+    ("honeydew", 0xF0FFF0), ("hotpink", 0xFF69B4), ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082), ("ivory", 0xFFFFF0), ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA), ("lavenderblush", 0xFFF0F5), ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD), ("lightblue", 0xADD8E6), ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF), ("lightgoldenrodyellow", 0xFAFAD2), ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90), ("lightgrey", 0xD3D3D3), ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A), ("lightseagreen", 0x20B2AA), ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899), ("lightslategrey", 0x778899), ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0), ("lime", 0x00FF00), ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6), ("magenta", 0xFF00FF), ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA), ("mediumblue", 0x0000CD), ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB), ("mediumseagreen", 0x3CB371), ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A), ("mediumturquoise", 0x48D1CC), ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970), ("mintcream", 0xF5FFFA), ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5), ("navajowhite", 0xFFDEAD), ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6), ("olive", 0x808000), ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500), ("orangered", 0xFF4500), ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA), ("palegreen", 0x98FB98), ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093), ("papayawhip", 0xFFEFD5), ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F), ("pink", 0xFFC0CB), ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6), ("purple", 0x800080), ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000), ("rosybrown", 0xBC8F8F), ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513), ("salmon", 0xFA8072), ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57), ("seashell", 0xFFF5EE), ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0), ("skyblue", 0x87CEEB), ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090), ("slategrey", 0x708090), ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F), ("steelblue", 0x4682B4), ("tan", 0xD2B48C),
+    ("teal", 0x008080), ("thistle", 0xD8BFD8), ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0), ("violet", 0xEE82EE), ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF), ("whitesmoke", 0xF5F5F5), ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
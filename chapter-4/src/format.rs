@@ -0,0 +1,129 @@
+use failure::Error;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+
+/// Wire format of request and response bodies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Cbor,
+    MessagePack,
+    Bincode,
+    Yaml,
+    Toml,
+    /// Newline-delimited JSON values, response only.
+    NdJson,
+    /// Concatenated CBOR values (RFC 8742), response only.
+    CborSeq,
+}
+
+
+/// `?format=` name and media types of each format, the first one is the canonical type.
+const FORMATS: &[(Format, &str, &[&str])] = &[
+    (Format::Json, "json", &["application/json", "text/json"]),
+    (Format::Cbor, "cbor", &["application/cbor"]),
+    (Format::MessagePack, "msgpack",
+     &["application/msgpack", "application/x-msgpack", "application/vnd.msgpack"]),
+    (Format::Bincode, "bincode", &["application/x-bincode", "application/bincode"]),
+    (Format::Yaml, "yaml", &["application/yaml", "application/x-yaml", "text/yaml"]),
+    (Format::Toml, "toml", &["application/toml"]),
+    (Format::NdJson, "ndjson", &["application/x-ndjson", "application/ndjson"]),
+    (Format::CborSeq, "cbor-seq", &["application/cbor-seq"]),
+];
+
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        FORMATS.iter()
+            .find(|(_, n, _)| *n == name)
+            .map(|(format, _, _)| *format)
+    }
+
+    /// Parses a media type, ignoring its parameters (`; charset=utf-8`).
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let mime = mime.split(';').next().unwrap_or("").trim().to_lowercase();
+        FORMATS.iter()
+            .find(|(_, _, mimes)| mimes.contains(&mime.as_str()))
+            .map(|(format, _, _)| *format)
+    }
+
+    /// Picks the supported format the client prefers most in an `Accept` header.
+    ///
+    /// Wildcards resolve to JSON. Returns `None` if nothing acceptable is supported.
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        let mut ranges = accept.split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let mime = parts.next()?.trim().to_lowercase();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .next()
+                    .map(|q| q.trim().parse::<f32>().unwrap_or(0.0))
+                    .unwrap_or(1.0);
+                Some((mime, quality))
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect::<Vec<_>>();
+        // stable, so equally preferred ranges keep the client's order
+        ranges.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+        ranges.iter()
+            .filter_map(|(mime, _)| match mime.as_str() {
+                "*/*" | "application/*" => Some(Format::Json),
+                mime => Format::from_mime(mime),
+            })
+            .next()
+    }
+
+    pub fn mime(self) -> &'static str {
+        FORMATS.iter()
+            .find(|(format, _, _)| *format == self)
+            .map(|(_, _, mimes)| mimes[0])
+            .unwrap()
+    }
+
+    /// Whether values are written one after another instead of as one document.
+    pub fn is_sequence(self) -> bool {
+        matches!(self, Format::NdJson | Format::CborSeq)
+    }
+
+    /// Whether the format can carry a request body.
+    ///
+    /// Bincode isn't self-describing, so it can't express the tagged and
+    /// free-form values of a request.
+    pub fn is_readable(self) -> bool {
+        !self.is_sequence() && self != Format::Bincode
+    }
+
+    /// Serializes a value; for sequence formats that is one element of the sequence.
+    pub fn serialize<T: Serialize>(self, value: &T) -> Result<Vec<u8>, Error> {
+        let data = match self {
+            Format::Json => serde_json::to_vec(value)?,
+            Format::Cbor | Format::CborSeq => serde_cbor::to_vec(value)?,
+            Format::MessagePack => rmp_serde::to_vec_named(value)?,
+            Format::Bincode => bincode::serialize(value)?,
+            Format::Yaml => serde_yaml::to_string(value)?.into_bytes(),
+            Format::Toml => toml::to_vec(value)?,
+            Format::NdJson => {
+                let mut data = serde_json::to_vec(value)?;
+                data.push(b'\n');
+                data
+            },
+        };
+        Ok(data)
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(self, data: &[u8]) -> Result<T, Error> {
+        let value = match self {
+            Format::Json => serde_json::from_slice(data)?,
+            Format::Cbor => serde_cbor::from_slice(data)?,
+            Format::MessagePack => rmp_serde::from_slice(data)?,
+            Format::Yaml => serde_yaml::from_slice(data)?,
+            Format::Toml => toml::from_slice(data)?,
+            Format::Bincode | Format::NdJson | Format::CborSeq => {
+                return Err(format_err!("{} can't be used for requests", self.mime()));
+            },
+        };
+        Ok(value)
+    }
+}
@@ -12,33 +12,46 @@ extern crate base64;
 extern crate base64_serde;
 extern crate queryst;
 extern crate serde_cbor;
+extern crate rmp_serde;
+extern crate bincode;
+extern crate serde_yaml;
+extern crate toml;
 
 mod color;
+mod format;
 
 use hyper::{Body, Response, Server, Method, Request, StatusCode};
 use hyper::service::service_fn;
-use futures::{future, Stream, Future};
-use rand::Rng;
-use rand::distributions::{Uniform, Normal, Bernoulli};
+use futures::{future, stream, Stream, Future};
+use hyper::header::{ACCEPT, CONTENT_TYPE};
+use rand::{Rng, SeedableRng};
+use rand::distributions::{Uniform, Normal, Bernoulli, Exp, Poisson, LogNormal, Gamma,
+                          Alphanumeric};
+use rand::seq;
+use rand::prng::ChaChaRng;
 use core::ops::Range;
 use std::cmp::{min, max};
 use base64::STANDARD;
-use color::Color;
+use color::{Color, ColorSpace, Notation};
+use format::Format;
 use failure::Error;
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeMap;
 use serde_json::Value;
 
 
-#[derive(Serialize)]
-#[serde(rename_all = "lowercase")]
 enum RngResponse {
     Value(f64),
-    #[serde(with = "Base64Standard")]
     Bytes(Vec<u8>),
-    Color(Color),
+    Color(Color, Option<Notation>),
+    Item(Value),
+    Items(Vec<Value>),
+    Token(String),
+    Uuid(String),
 }
 
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize)]
 #[serde(tag = "distribution", content = "parameters", rename_all = "lowercase")]
 enum RngRequest {
     Uniform {
@@ -56,10 +69,231 @@ enum RngRequest {
         #[serde(with = "Base64Standard")]
         data: Vec<u8>,
     },
+    /// Without `space` every channel is drawn independently between the
+    /// channels of `from` and `to`. With `space` the color is drawn from the
+    /// gradient between `from` and `to` in that color space.
     Color {
         from: Color,
         to: Color,
+        space: Option<ColorSpace>,
+        notation: Option<Notation>,
+    },
+    Exponential {
+        lambda: f64,
+    },
+    Poisson {
+        lambda: f64,
+    },
+    LogNormal {
+        mean: f64,
+        std_dev: f64,
+    },
+    Gamma {
+        shape: f64,
+        scale: f64,
     },
+    Beta {
+        alpha: f64,
+        beta: f64,
+    },
+    /// One of `items`, picked with probability proportional to its weight
+    /// (all items are equally likely without `weights`).
+    Choice {
+        items: Vec<Value>,
+        weights: Option<Vec<f64>>,
+    },
+    /// `amount` distinct items, without replacement.
+    Sample {
+        items: Vec<Value>,
+        amount: usize,
+    },
+    /// Random alphanumeric string of `length` characters.
+    Token {
+        length: usize,
+    },
+    /// Random (version 4) UUID.
+    Uuid,
+}
+
+
+/// Longest string generated by `RngRequest::Token`.
+const MAX_TOKEN: usize = 4096;
+
+/// Largest mean, or standard deviation, of a distribution drawing floats.
+/// Draws of `rand` stay within a few hundred times of it, far from overflowing.
+const MAX_MEAN: f64 = 1e300;
+
+/// Normal draws of `rand` stay within this many standard deviations of the mean.
+const MAX_DEVIATIONS: f64 = 14.0;
+
+/// Largest Poisson mean, draws stay well below `u64::MAX`.
+const MAX_POISSON: f64 = 1e18;
+
+
+impl RngRequest {
+    /// Checks the parameters, so `handle_request` never gets a distribution
+    /// that `rand` would refuse to construct.
+    fn validate(&self) -> Result<(), Error> {
+        match self {
+            RngRequest::Uniform { range } => {
+                ensure!(range.start < range.end, "uniform: start must be less than end");
+            },
+            RngRequest::Normal { mean, std_dev } | RngRequest::LogNormal { mean, std_dev } => {
+                ensure!(mean.is_finite(), "mean must be a finite number");
+                ensure!(std_dev.is_finite() && *std_dev >= 0.0, "std_dev must not be negative");
+                ensure!(mean.abs() <= MAX_MEAN && *std_dev <= MAX_MEAN,
+                        "mean and std_dev must be at most {:e} in size", MAX_MEAN);
+                if let RngRequest::LogNormal { .. } = self {
+                    // its draws are the exponential of normal ones
+                    ensure!(mean + MAX_DEVIATIONS * std_dev <= f64::MAX.ln(),
+                            "lognormal: mean + {} * std_dev must be at most {}",
+                            MAX_DEVIATIONS, f64::MAX.ln());
+                }
+            },
+            RngRequest::Bernoulli { p } => {
+                ensure!((0.0..=1.0).contains(p), "bernoulli: p must be between 0 and 1");
+            },
+            RngRequest::Shuffle { .. } | RngRequest::Color { .. } | RngRequest::Uuid => {
+            },
+            RngRequest::Exponential { lambda } => {
+                ensure!(lambda.is_finite() && *lambda >= 1.0 / MAX_MEAN,
+                        "exponential: lambda must be at least {:e}", 1.0 / MAX_MEAN);
+            },
+            RngRequest::Poisson { lambda } => {
+                ensure!(lambda.is_finite() && *lambda > 0.0 && *lambda <= MAX_POISSON,
+                        "poisson: lambda must be positive and at most {:e}", MAX_POISSON);
+            },
+            RngRequest::Gamma { shape, scale } => {
+                ensure!(shape.is_finite() && *shape > 0.0, "gamma: shape must be positive");
+                ensure!(scale.is_finite() && *scale > 0.0, "gamma: scale must be positive");
+                // the mean, or the scale for shapes below 1, whose draws come from gamma(1)
+                ensure!(shape.max(1.0) * scale <= MAX_MEAN,
+                        "gamma: shape * scale must be at most {:e}", MAX_MEAN);
+            },
+            RngRequest::Beta { alpha, beta } => {
+                ensure!(alpha.is_finite() && *alpha > 0.0, "beta: alpha must be positive");
+                ensure!(beta.is_finite() && *beta > 0.0, "beta: beta must be positive");
+            },
+            RngRequest::Choice { items, weights } => {
+                ensure!(!items.is_empty(), "choice: items must not be empty");
+                if let Some(weights) = weights {
+                    ensure!(weights.len() == items.len(),
+                            "choice: {} weights given for {} items", weights.len(), items.len());
+                    ensure!(weights.iter().all(|w| w.is_finite() && *w >= 0.0),
+                            "choice: weights must not be negative");
+                    let total = weights.iter().sum::<f64>();
+                    ensure!(total > 0.0, "choice: at least one weight must be positive");
+                    ensure!(total.is_finite(), "choice: weights must add up to a finite number");
+                }
+            },
+            RngRequest::Sample { items, amount } => {
+                ensure!(*amount <= items.len(),
+                        "sample: can't take {} of {} items", amount, items.len());
+            },
+            RngRequest::Token { length } => {
+                ensure!(*length > 0 && *length <= MAX_TOKEN,
+                        "token: length must be between 1 and {}", MAX_TOKEN);
+            },
+        }
+        Ok(())
+    }
+}
+
+
+/// Envelope of a request: the distribution, an optional seed and an
+/// optional number of values to draw from it.
+///
+/// Requests with the same seed get the same values. Without a seed a random
+/// one is chosen, either way it is echoed in `SeededResponse`.
+#[derive(Deserialize)]
+struct SeededRequest {
+    seed: Option<u64>,
+    count: Option<usize>,
+    #[serde(flatten)]
+    request: RngRequest,
+}
+
+
+/// Largest seed, so it fits the signed 64-bit integers of TOML.
+const MAX_SEED: u64 = i64::MAX as u64;
+
+
+impl SeededRequest {
+    fn validate(&self) -> Result<(), Error> {
+        if let Some(seed) = self.seed {
+            ensure!(seed <= MAX_SEED, "seed must be at most {}", MAX_SEED);
+        }
+        self.request.validate()
+    }
+
+    /// The seed asked for, or a random one.
+    fn seed(&self) -> u64 {
+        self.seed.unwrap_or_else(|| rand::random::<u64>() & MAX_SEED)
+    }
+}
+
+
+/// Body of `POST /random`: a single request or an array of them.
+enum Batch {
+    Single(SeededRequest),
+    Many(Vec<SeededRequest>),
+}
+
+
+/// Largest batch rendered as a single JSON or CBOR array.
+const MAX_BATCH: usize = 10_000;
+/// Largest batch streamed as `ndjson` or `cbor-seq`.
+const MAX_STREAM: usize = 100_000;
+/// Number of values written to the response body at once when streaming.
+const STREAM_CHUNK: usize = 256;
+
+
+/// Serialized as a map of `seed` and one more key naming the kind of the
+/// value, e.g. `{"seed": 7, "value": 15.0}`.
+struct SeededResponse {
+    seed: u64,
+    response: RngResponse,
+}
+
+
+// Written by hand rather than with `#[serde(flatten)]`, which needs a
+// self-describing format and would rule out bincode.
+impl Serialize for SeededResponse {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        struct Bytes<'a>(&'a [u8]);
+
+        impl<'a> Serialize for Bytes<'a> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                Base64Standard::serialize(self.0, serializer)
+            }
+        }
+
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("seed", &self.seed)?;
+        match &self.response {
+            RngResponse::Value(value) => map.serialize_entry("value", value)?,
+            RngResponse::Bytes(bytes) => map.serialize_entry("bytes", &Bytes(bytes))?,
+            RngResponse::Color(color, None) => map.serialize_entry("color", color)?,
+            RngResponse::Color(color, Some(notation)) => {
+                map.serialize_entry("color", &color.to_notation(*notation))?
+            },
+            RngResponse::Item(item) => map.serialize_entry("item", item)?,
+            RngResponse::Items(items) => map.serialize_entry("items", items)?,
+            RngResponse::Token(token) => map.serialize_entry("token", token)?,
+            RngResponse::Uuid(uuid) => map.serialize_entry("uuid", uuid)?,
+        }
+        map.end()
+    }
+}
+
+
+/// TOML documents are tables, so arrays of responses are put under `values`.
+#[derive(Serialize)]
+struct Values<T> {
+    values: T,
 }
 
 
@@ -73,21 +307,20 @@ fn handler(req: Request<Body>)
     let path = req.uri().path();
     match (method, path) {
         (&Method::POST, "/random") => {
-            let format = {
-                let uri = req.uri().query().unwrap_or("");
-                let query = queryst::parse(uri).unwrap_or(Value::Null);
-                query["format"].as_str().unwrap_or("json").to_string()
+            let output = match response_format(&req) {
+                Ok(format) => format,
+                Err(err) => return response_with_error(StatusCode::NOT_ACCEPTABLE, err),
+            };
+            let input = match request_format(&req) {
+                Ok(format) => format,
+                Err(err) => return response_with_error(StatusCode::UNSUPPORTED_MEDIA_TYPE, err),
             };
             let body = req.into_body().concat2()
                 .map(move |chunks| {
-                    let res = serde_json::from_slice::<RngRequest>(chunks.as_ref())
-                        .map(handle_request)
-                        .map_err(Error::from)
-                        .and_then(move |resp| serialize(&format, &resp));
+                    let res = parse_batch(input, chunks.as_ref())
+                        .and_then(move |batch| handle_batch(output, batch));
                     match res {
-                        Ok(body) => {
-                            Response::new(body.into())
-                        },
+                        Ok(resp) => resp,
                         Err(err) => {
                             Response::builder()
                                 .status(StatusCode::UNPROCESSABLE_ENTITY)
@@ -122,8 +355,153 @@ fn response_with_code(status_code: StatusCode)
 }
 
 
-fn handle_request(request: RngRequest) -> RngResponse {
-    let mut rng = rand::thread_rng();
+fn response_with_error(status_code: StatusCode, err: Error)
+    -> Box<dyn Future<Item=Response<Body>, Error=hyper::Error> + Send>
+{
+    let body = Response::builder()
+        .status(status_code)
+        .body(err.to_string().into())
+        .unwrap();
+    Box::new(future::ok(body))
+}
+
+
+/// Format of the response: the `format` query parameter if there is one,
+/// otherwise the best match of the `Accept` header, JSON if there is none.
+fn response_format(req: &Request<Body>) -> Result<Format, Error> {
+    let query = queryst::parse(req.uri().query().unwrap_or("")).unwrap_or(Value::Null);
+    if let Some(name) = query["format"].as_str() {
+        return Format::from_name(name)
+            .ok_or_else(|| format_err!("unsupported format {}", name));
+    }
+    match req.headers().get(ACCEPT) {
+        Some(accept) => {
+            let accept = accept.to_str()?;
+            Format::from_accept(accept)
+                .ok_or_else(|| format_err!("none of {} is supported", accept))
+        },
+        None => Ok(Format::Json),
+    }
+}
+
+
+/// Format of the request body by its `Content-Type`.
+///
+/// Bodies without a type, or with the form type `curl -d` sends by default,
+/// are read as JSON like they were before negotiation was supported.
+fn request_format(req: &Request<Body>) -> Result<Format, Error> {
+    match req.headers().get(CONTENT_TYPE) {
+        Some(content_type) => {
+            let content_type = content_type.to_str()?;
+            if content_type.starts_with("application/x-www-form-urlencoded") {
+                return Ok(Format::Json);
+            }
+            Format::from_mime(content_type)
+                .filter(|format| format.is_readable())
+                .ok_or_else(|| format_err!("unsupported content type {}", content_type))
+        },
+        None => Ok(Format::Json),
+    }
+}
+
+
+/// Reads the body into a generic value first to tell a single request from an array.
+fn parse_batch(format: Format, data: &[u8]) -> Result<Batch, Error> {
+    let value = format.deserialize::<Value>(data)?;
+    let batch = if value.is_array() {
+        Batch::Many(serde_json::from_value(value)?)
+    } else {
+        Batch::Single(serde_json::from_value(value)?)
+    };
+    match &batch {
+        Batch::Single(request) => request.validate()?,
+        Batch::Many(requests) => {
+            for request in requests {
+                request.validate()?;
+            }
+        },
+    }
+    Ok(batch)
+}
+
+
+/// Answers a single request without `count` with one `SeededResponse`, and
+/// anything else with a sequence of them: an array for document formats,
+/// or a stream of values for `ndjson` and `cbor-seq`.
+fn handle_batch(format: Format, batch: Batch) -> Result<Response<Body>, Error> {
+    let requests = match batch {
+        Batch::Single(request) if request.count.is_none() => {
+            let body = format.serialize(&handle_seeded(request))?;
+            return response_with_body(format, body.into());
+        },
+        Batch::Single(request) => vec![request],
+        Batch::Many(requests) => requests,
+    };
+    let total = requests.iter()
+        .fold(0usize, |total, request| total.saturating_add(request.count.unwrap_or(1)));
+    if format.is_sequence() {
+        if total > MAX_STREAM {
+            return Err(format_err!("{} values requested, at most {} can be streamed",
+                                   total, MAX_STREAM));
+        }
+        let chunks = stream::iter_ok::<_, Error>(samples(requests))
+            .chunks(STREAM_CHUNK)
+            .and_then(move |values| {
+                let mut chunk = Vec::new();
+                for value in values {
+                    chunk.extend(format.serialize(&value)?);
+                }
+                Ok(chunk)
+            })
+            .map_err(|err| err.compat());
+        response_with_body(format, Body::wrap_stream(chunks))
+    } else {
+        if total > MAX_BATCH {
+            return Err(format_err!("{} values requested, use ndjson or cbor-seq for more than {}",
+                                   total, MAX_BATCH));
+        }
+        let values = samples(requests).collect::<Vec<_>>();
+        let body = match format {
+            Format::Toml => format.serialize(&Values { values })?,
+            _ => format.serialize(&values)?,
+        };
+        response_with_body(format, body.into())
+    }
+}
+
+
+fn response_with_body(format: Format, body: Body) -> Result<Response<Body>, Error> {
+    let resp = Response::builder()
+        .header(CONTENT_TYPE, format.mime())
+        .body(body)?;
+    Ok(resp)
+}
+
+
+/// Lazily draws `count` values for every request, each request from its own seeded stream.
+fn samples(requests: Vec<SeededRequest>) -> impl Iterator<Item=SeededResponse> + Send {
+    requests.into_iter().flat_map(|request| {
+        let seed = request.seed();
+        let mut rng = ChaChaRng::seed_from_u64(seed);
+        let count = request.count.unwrap_or(1);
+        let request = request.request;
+        (0..count).map(move |_| {
+            let response = handle_request(request.clone(), &mut rng);
+            SeededResponse { seed, response }
+        })
+    })
+}
+
+
+fn handle_seeded(request: SeededRequest) -> SeededResponse {
+    let seed = request.seed();
+    let mut rng = ChaChaRng::seed_from_u64(seed);
+    let response = handle_request(request.request, &mut rng);
+    SeededResponse { seed, response }
+}
+
+
+fn handle_request<R: Rng>(request: RngRequest, rng: &mut R) -> RngResponse {
     match request {
         RngRequest::Uniform { range } => {
             let value = rng.sample(Uniform::from(range)) as f64;
@@ -141,34 +519,100 @@ fn handle_request(request: RngRequest) -> RngResponse {
             rng.shuffle(&mut data);
             RngResponse::Bytes(data)
         },
-        RngRequest::Color { from, to } => {
+        RngRequest::Color { from, to, space: None, notation } => {
             let red = rng.sample(color_range(from.red, to.red));
             let green = rng.sample(color_range(from.green, to.green));
             let blue = rng.sample(color_range(from.blue, to.blue));
-            RngResponse::Color(Color { red, green, blue })
+            let alpha = rng.sample(color_range(from.alpha, to.alpha));
+            RngResponse::Color(Color { red, green, blue, alpha }, notation)
+        },
+        RngRequest::Color { from, to, space: Some(space), notation } => {
+            let t = rng.gen::<f64>();
+            RngResponse::Color(from.interpolate(&to, t, space), notation)
+        },
+        RngRequest::Exponential { lambda } => {
+            RngResponse::Value(rng.sample(Exp::new(lambda)))
+        },
+        RngRequest::Poisson { lambda } => {
+            let value = rng.sample::<u64, _>(Poisson::new(lambda)) as f64;
+            RngResponse::Value(value)
+        },
+        RngRequest::LogNormal { mean, std_dev } => {
+            RngResponse::Value(rng.sample(LogNormal::new(mean, std_dev)))
+        },
+        RngRequest::Gamma { shape, scale } => {
+            RngResponse::Value(rng.sample(Gamma::new(shape, scale)))
+        },
+        RngRequest::Beta { alpha, beta } => {
+            RngResponse::Value(sample_beta(rng, alpha, beta))
+        },
+        RngRequest::Choice { mut items, weights } => {
+            let index = match weights {
+                Some(weights) => weighted_index(rng, &weights),
+                None => rng.gen_range(0, items.len()),
+            };
+            RngResponse::Item(items.swap_remove(index))
+        },
+        RngRequest::Sample { items, amount } => {
+            RngResponse::Items(seq::sample_iter(rng, items, amount).unwrap_or_default())
+        },
+        RngRequest::Token { length } => {
+            let token = rng.sample_iter(&Alphanumeric).take(length).collect();
+            RngResponse::Token(token)
+        },
+        RngRequest::Uuid => {
+            let mut bytes = [0u8; 16];
+            rng.fill(&mut bytes);
+            bytes[6] = (bytes[6] & 0x0F) | 0x40;
+            bytes[8] = (bytes[8] & 0x3F) | 0x80;
+            let hex = bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+            let uuid = format!("{}-{}-{}-{}-{}",
+                               &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32]);
+            RngResponse::Uuid(uuid)
         },
     }
 }
 
 
-fn color_range(from: u8, to: u8) -> Uniform<u8> {
-    let (from, to) = (min(from, to), max(from, to));
-    Uniform::new_inclusive(from, to)
+/// Draws of the two gamma values before `sample_beta` gives up on them.
+const BETA_DRAWS: usize = 16;
+
+
+/// X / (X + Y) with X ~ Gamma(alpha, 1) and Y ~ Gamma(beta, 1) is Beta(alpha, beta).
+///
+/// With tiny shapes both draws may underflow to 0, they're drawn again then.
+/// If they keep doing so the value is 0 or 1, where such a Beta piles up.
+fn sample_beta<R: Rng>(rng: &mut R, alpha: f64, beta: f64) -> f64 {
+    for _ in 0..BETA_DRAWS {
+        let x = rng.sample(Gamma::new(alpha, 1.0));
+        let y = rng.sample(Gamma::new(beta, 1.0));
+        if x > 0.0 || y > 0.0 {
+            // rather than x / (x + y), which is NaN if the sum overflows
+            return 1.0 / (1.0 + y / x);
+        }
+    }
+    if rng.gen_bool(alpha / (alpha + beta)) { 1.0 } else { 0.0 }
 }
 
 
-fn serialize(format: &str, resp: &RngResponse) -> Result<Vec<u8>, Error> {
-    match format {
-        "json" => {
-            Ok(serde_json::to_vec(resp)?)
-        },
-        "cbor" => {
-            Ok(serde_cbor::to_vec(resp)?)
-        },
-        _ => {
-            Err(format_err!("unsupported format {}", format))
-        },
+/// Index of a weight picked with probability proportional to it.
+fn weighted_index<R: Rng>(rng: &mut R, weights: &[f64]) -> usize {
+    let total: f64 = weights.iter().sum();
+    let mut point = rng.gen_range(0.0, total);
+    for (index, weight) in weights.iter().enumerate() {
+        if point < *weight {
+            return index;
+        }
+        point -= weight;
     }
+    // rounding may leave `point` just above the last weight
+    weights.iter().rposition(|w| *w > 0.0).unwrap_or(0)
+}
+
+
+fn color_range(from: u8, to: u8) -> Uniform<u8> {
+    let (from, to) = (min(from, to), max(from, to));
+    Uniform::new_inclusive(from, to)
 }
 
 
@@ -0,0 +1,41 @@
+use futures::future;
+use hyper::{Body, Request, Response, StatusCode};
+use hyper::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use crate::config::Config;
+use crate::{message_response, ResponseFuture};
+
+
+/// Checks the bearer token of a call to an admin endpoint: listing files,
+/// deleting them and signing download links.
+///
+/// Returns the response to refuse the call with.
+pub fn authorize(req: &Request<Body>, config: &Config) -> Result<(), ResponseFuture> {
+    let token = match config.admin_token {
+        Some(ref token) => token,
+        None => {
+            let message = "admin endpoints need ADMIN_TOKEN to be set".to_owned();
+            return Err(Box::new(future::ok(message_response(StatusCode::FORBIDDEN, message))));
+        },
+    };
+    let given = req.headers().get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match given {
+        Some(given) if constant_time_eq(given.as_bytes(), token.as_bytes()) => Ok(()),
+        _ => {
+            let resp = Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .header(WWW_AUTHENTICATE, "Bearer")
+                .body(Body::empty())
+                .unwrap();
+            Err(Box::new(future::ok(resp)))
+        },
+    }
+}
+
+
+/// Compares every byte, so the time taken doesn't tell how much of a guess
+/// was right. Only the length may leak.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
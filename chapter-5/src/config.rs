@@ -0,0 +1,128 @@
+use std::env;
+use std::path::PathBuf;
+use crate::encoding::Encoding;
+use crate::storage::S3Config;
+
+
+const DEFAULT_MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+const DEFAULT_ALLOWED_TYPES: &str =
+    "image/*,text/plain,application/pdf,application/zip,application/gzip,application/zstd,application/x-tar";
+
+
+/// Backend for stored files and their metadata, `STORAGE`.
+#[derive(Clone, Debug)]
+pub enum StorageConfig {
+    /// `local`, the default: files under `files`.
+    Local,
+    /// `memory`: lost on restart, for tests.
+    Memory,
+    /// `s3`: a bucket set with `S3_ENDPOINT`, `S3_BUCKET`, `S3_REGION`,
+    /// `S3_ACCESS_KEY` and `S3_SECRET_KEY`. Replicas can share it.
+    S3(S3Config),
+}
+
+
+/// Settings of the service, read from the environment at startup.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Directory for uploads in progress and for the local backend, `FILES_DIR`.
+    pub files: PathBuf,
+    pub storage: StorageConfig,
+    /// Largest accepted upload in bytes, `MAX_UPLOAD_SIZE`.
+    pub max_size: u64,
+    /// Media types accepted for uploads, `ALLOWED_TYPES` as a comma separated
+    /// list. Entries may end with `/*` to allow all subtypes.
+    pub allowed_types: Vec<String>,
+    /// Key for signing download links, `LINK_SECRET`. Once it's set,
+    /// downloads are only served through signed links.
+    pub link_secret: Option<String>,
+    /// Bearer token of the admin endpoints, `ADMIN_TOKEN`: listing files,
+    /// deleting them and signing download links. They are refused without it.
+    pub admin_token: Option<String>,
+    /// Codings files are stored with by media type, `COMPRESS` as a comma
+    /// separated list of `type=coding` pairs, like `text/*=zstd`. The first
+    /// matching pair wins, other files are stored as they are.
+    pub compress: Vec<(String, Encoding)>,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, String> {
+        let max_size = match env::var("MAX_UPLOAD_SIZE") {
+            Ok(size) => size.parse()
+                .map_err(|err| format!("invalid MAX_UPLOAD_SIZE {}: {}", size, err))?,
+            Err(_) => DEFAULT_MAX_SIZE,
+        };
+        let allowed_types = env::var("ALLOWED_TYPES")
+            .unwrap_or_else(|_| DEFAULT_ALLOWED_TYPES.to_owned())
+            .split(',')
+            .map(|mime| mime.trim().to_lowercase())
+            .filter(|mime| !mime.is_empty())
+            .collect();
+        let compress = env::var("COMPRESS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                pair.split_once('=')
+                    .and_then(|(mime, coding)| {
+                        let encoding = Encoding::from_name(coding.trim())?;
+                        Some((mime.trim().to_lowercase(), encoding))
+                    })
+                    .ok_or_else(|| {
+                        format!("invalid COMPRESS entry {}, expected type=gzip|br|zstd", pair)
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        let storage = match env::var("STORAGE").as_ref().map(String::as_str) {
+            Ok("local") | Err(_) => StorageConfig::Local,
+            Ok("memory") => StorageConfig::Memory,
+            Ok("s3") => StorageConfig::S3(S3Config {
+                endpoint: required_var("S3_ENDPOINT")?,
+                bucket: required_var("S3_BUCKET")?,
+                region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_owned()),
+                access_key: required_var("S3_ACCESS_KEY")?,
+                secret_key: required_var("S3_SECRET_KEY")?,
+            }),
+            Ok(other) => {
+                return Err(format!("unknown STORAGE {}, expected local, memory or s3", other));
+            },
+        };
+        Ok(Config {
+            files: PathBuf::from(env::var("FILES_DIR").unwrap_or_else(|_| "./files".to_owned())),
+            storage,
+            max_size,
+            allowed_types,
+            link_secret: env::var("LINK_SECRET").ok().filter(|secret| !secret.is_empty()),
+            admin_token: env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty()),
+            compress,
+        })
+    }
+
+    pub fn is_allowed(&self, mime: &str) -> bool {
+        self.allowed_types.iter().any(|allowed| matches_type(allowed, mime))
+    }
+
+    /// The coding to store a file of type `mime` with, if any.
+    pub fn encoding_for(&self, mime: &str) -> Option<Encoding> {
+        self.compress.iter()
+            .find(|(pattern, _)| matches_type(pattern, mime))
+            .map(|(_, encoding)| *encoding)
+    }
+}
+
+
+/// Matches a media type against a pattern that may end with `/*`.
+fn matches_type(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some("*") => true,
+        Some(kind) => mime.split('/').next() == Some(kind),
+        None => pattern == mime,
+    }
+}
+
+
+fn required_var(name: &str) -> Result<String, String> {
+    env::var(name).map_err(|_| format!("{} is required for the s3 storage", name))
+}
@@ -0,0 +1,74 @@
+use futures::{Async, Poll, Stream};
+use hyper::Chunk;
+use sha2::{Digest, Sha256};
+use std::io::{Error, ErrorKind};
+use crate::encoding::Coder;
+
+
+/// Files are stored under the lowercase hex SHA-256 of their content.
+pub fn to_hex(hasher: Sha256) -> String {
+    hex::encode(hasher.result())
+}
+
+
+/// Passes the chunks of a stored file through and hashes them on the way.
+///
+/// The stream fails at the end instead of finishing if the content doesn't
+/// match its digest, so the client never sees a complete, corrupted body.
+pub struct VerifyStream<S> {
+    inner: S,
+    hasher: Sha256,
+    digest: String,
+    decoder: Option<Coder>,
+}
+
+impl<S> VerifyStream<S> {
+    pub fn new(inner: S, digest: String) -> Self {
+        VerifyStream {
+            inner,
+            hasher: Sha256::new(),
+            digest,
+            decoder: None,
+        }
+    }
+
+    /// Verifies compressed chunks, which are passed through as they are.
+    pub fn decoding(inner: S, digest: String, decoder: Coder) -> Self {
+        VerifyStream {
+            decoder: Some(decoder),
+            ..VerifyStream::new(inner, digest)
+        }
+    }
+}
+
+impl<S> Stream for VerifyStream<S>
+where
+    S: Stream<Item=Chunk, Error=Error>,
+{
+    type Item = Chunk;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, Error> {
+        match self.inner.poll()? {
+            Async::Ready(Some(chunk)) => {
+                match self.decoder {
+                    Some(ref mut decoder) => self.hasher.input(&decoder.write(&chunk)?),
+                    None => self.hasher.input(&chunk),
+                }
+                Ok(Async::Ready(Some(chunk)))
+            },
+            Async::Ready(None) => {
+                if let Some(decoder) = self.decoder.take() {
+                    self.hasher.input(&decoder.finish()?);
+                }
+                let actual = to_hex(self.hasher.clone());
+                if actual != self.digest {
+                    let msg = format!("content of {} has digest {}", self.digest, actual);
+                    return Err(Error::new(ErrorKind::InvalidData, msg));
+                }
+                Ok(Async::Ready(None))
+            },
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use futures::{stream, Future, Stream};
+use hyper::{Body, Chunk, HeaderMap, Method, Request, Response, StatusCode};
+use hyper::header::{ACCEPT_RANGES, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+ETAG, HeaderName, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE, VARY};
+use rand::{Rng, thread_rng};
+use rand::distributions::Alphanumeric;
+use std::io::Error;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::digest::VerifyStream;
+use crate::encoding::{CodedStream, Encoding};
+use crate::meta::{self, Meta};
+use crate::storage::{ByteRange, ByteStream, Limit, SharedStorage, Skip};
+use crate::{empty_response, ResponseFuture};
+
+
+/// Requests for more ranges than this get the whole file.
+const MAX_RANGES: usize = 16;
+
+
+fn content_range(range: &ByteRange, total: u64) -> String {
+    format!("bytes {}-{}/{}", range.start, range.end, total)
+}
+
+
+pub enum Ranges {
+    Full,
+    Partial(Vec<ByteRange>),
+    Unsatisfiable,
+}
+
+
+/// Parses a `Range` header for a file of `total` bytes.
+///
+/// Headers that can't be parsed, use another unit than `bytes` or ask for
+/// too many ranges are ignored, as RFC 7233 allows.
+pub fn parse_ranges(header: &str, total: u64) -> Ranges {
+    let specs = match header.trim().strip_prefix("bytes=") {
+        Some(specs) => specs,
+        None => return Ranges::Full,
+    };
+    let mut ranges = Vec::new();
+    for spec in specs.split(',') {
+        let (start, end) = match spec.trim().split_once('-') {
+            Some(bounds) => bounds,
+            None => return Ranges::Full,
+        };
+        let range = match (start.parse::<u64>(), end) {
+            (Ok(start), "") => Some((start, total.saturating_sub(1))),
+            (Ok(start), end) => match end.parse::<u64>() {
+                Ok(end) if end >= start => Some((start, end.min(total.saturating_sub(1)))),
+                _ => return Ranges::Full,
+            },
+            (Err(_), _) if start.is_empty() => match end.parse::<u64>() {
+                Ok(0) => None,
+                Ok(suffix) => Some((total.saturating_sub(suffix), total.saturating_sub(1))),
+                Err(_) => return Ranges::Full,
+            },
+            (Err(_), _) => return Ranges::Full,
+        };
+        // ranges starting past the end are skipped, the rest is still served
+        if let Some((start, end)) = range {
+            if start < total {
+                ranges.push(ByteRange { start, end });
+            }
+        }
+    }
+    if ranges.len() > MAX_RANGES {
+        Ranges::Full
+    } else if ranges.is_empty() {
+        Ranges::Unsatisfiable
+    } else {
+        Ranges::Partial(ranges)
+    }
+}
+
+
+/// Streams a range of a file, of its decoded content if it's `decoded` from
+/// that coding: it's decoded from the start and the bytes before are dropped.
+fn object_range(storage: &SharedStorage, key: &str, range: ByteRange, decoded: Option<Encoding>)
+    -> ByteStream
+{
+    match decoded {
+        Some(encoding) => {
+            let body = storage.get(key, None).flatten_stream();
+            let body = Skip::new(CodedStream::new(body, encoding.decoder()), range.start);
+            Box::new(Limit::new(body, range.len()))
+        },
+        None => Box::new(storage.get(key, Some(range)).flatten_stream()),
+    }
+}
+
+
+/// Builds a `multipart/byteranges` body, returns its content type, length and stream.
+fn multipart(
+    storage: &SharedStorage,
+    key: &str,
+    ranges: Vec<ByteRange>,
+    total: u64,
+    content_type: &str,
+    decoded: Option<Encoding>,
+)
+    -> (String, u64, impl Stream<Item=Chunk, Error=Error>)
+{
+    let boundary: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(20)
+        .collect();
+    let mut length = 0;
+    let parts = ranges.into_iter()
+        .enumerate()
+        .map(|(idx, range)| {
+            let head = format!(
+                "{}--{}\r\n{}: {}\r\n{}: {}\r\n\r\n",
+                if idx == 0 { "" } else { "\r\n" },
+                boundary,
+                CONTENT_TYPE, content_type,
+                CONTENT_RANGE, content_range(&range, total),
+            );
+            length += head.len() as u64 + range.len();
+            let data = object_range(storage, key, range, decoded);
+            stream::once(Ok(Chunk::from(head))).chain(data)
+        })
+        .collect::<Vec<_>>();
+    let tail = format!("\r\n--{}--\r\n", boundary);
+    length += tail.len() as u64;
+    let body = stream::iter_ok::<_, Error>(parts)
+        .flatten()
+        .chain(stream::once(Ok(Chunk::from(tail))));
+    let multipart_type = format!("multipart/byteranges; boundary={}", boundary);
+    (multipart_type, length, body)
+}
+
+
+/// HTTP dates have a resolution of seconds.
+fn truncate_to_secs(time: SystemTime) -> SystemTime {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+
+fn header_str(headers: &HeaderMap, name: HeaderName) -> Option<&str> {
+    headers.get(&name).and_then(|value| value.to_str().ok())
+}
+
+
+/// Whether the client's copy is current, by `If-None-Match`, or by
+/// `If-Modified-Since` if the former is missing.
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified: SystemTime) -> bool {
+    if let Some(tags) = header_str(headers, IF_NONE_MATCH) {
+        return tags.split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+    }
+    header_str(headers, IF_MODIFIED_SINCE)
+        .and_then(|since| httpdate::parse_http_date(since).ok())
+        .is_some_and(|since| modified <= since)
+}
+
+
+/// Whether a `Range` request may be served partially, `If-Range` has to
+/// match the current version of the file exactly.
+fn is_range_current(headers: &HeaderMap, etag: &str, modified: SystemTime) -> bool {
+    match header_str(headers, IF_RANGE) {
+        None => true,
+        Some(tag) if tag.starts_with('"') => tag == etag,
+        Some(date) => httpdate::parse_http_date(date).ok() == Some(modified),
+    }
+}
+
+
+/// Serves a stored file for `GET` and `HEAD`.
+///
+/// Sets `ETag` (the quoted digest), `Last-Modified` and `Content-Length`,
+/// answers conditional requests with `304 Not Modified` and `Range` requests
+/// with `206 Partial Content`, as `multipart/byteranges` for several ranges.
+/// Only whole files are verified against their digest.
+///
+/// Compressed files are sent as they are stored to clients that accept their
+/// coding, with ranges of the compressed bytes and the coding in the `ETag`.
+/// Other clients get them decoded on the fly, with ranges of the decoded
+/// bytes, which means decoding everything before a range too.
+pub fn serve(req: &Request<Body>, storage: &SharedStorage, digest: String, meta: Option<Meta>)
+    -> ResponseFuture
+{
+    let head = req.method() == Method::HEAD;
+    let headers = req.headers().clone();
+    let storage = storage.clone();
+    let content_type = meta::content_type(&meta);
+    let encoding = meta.as_ref().and_then(|meta| meta.encoding);
+    let (passed, decoded) = match encoding {
+        Some(encoding) if encoding.is_accepted(&headers) => (Some(encoding), None),
+        Some(encoding) => (None, meta.map(|meta| (encoding, meta.size))),
+        None => (None, None),
+    };
+    let resp = storage.stat(&digest).map(move |info| {
+        let info = match info {
+            Some(info) => info,
+            None => return empty_response(StatusCode::NOT_FOUND),
+        };
+        let total = decoded.map_or(info.size, |(_, size)| size);
+        let etag = match passed {
+            Some(encoding) => format!("\"{}-{}\"", digest, encoding),
+            None => format!("\"{}\"", digest),
+        };
+        let modified = truncate_to_secs(info.modified);
+        let mut builder = Response::builder();
+        builder
+            .header(ETAG, etag.as_str())
+            .header(ACCEPT_RANGES, "bytes")
+            .header(LAST_MODIFIED, httpdate::fmt_http_date(modified).as_str());
+        if encoding.is_some() {
+            builder.header(VARY, "Accept-Encoding");
+        }
+        if let Some(encoding) = passed {
+            builder.header(CONTENT_ENCODING, encoding.name());
+        }
+        if is_not_modified(&headers, &etag, modified) {
+            let resp = builder
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())
+                .unwrap();
+            return resp;
+        }
+        let ranges = match header_str(&headers, RANGE) {
+            Some(range) if is_range_current(&headers, &etag, modified) => {
+                parse_ranges(range, total)
+            },
+            _ => Ranges::Full,
+        };
+        let coding = decoded.map(|(encoding, _)| encoding);
+        let (length, body): (u64, ByteStream) = match ranges {
+            Ranges::Unsatisfiable => {
+                let resp = builder
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(CONTENT_RANGE, format!("bytes */{}", total).as_str())
+                    .body(Body::empty())
+                    .unwrap();
+                return resp;
+            },
+            Ranges::Partial(ref ranges) if ranges.len() == 1 => {
+                let range = ranges[0];
+                builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(CONTENT_TYPE, content_type.as_str())
+                    .header(CONTENT_RANGE, content_range(&range, total).as_str());
+                (range.len(), object_range(&storage, &digest, range, coding))
+            },
+            Ranges::Partial(ranges) => {
+                let (multipart_type, length, body) =
+                    multipart(&storage, &digest, ranges, total, &content_type, coding);
+                builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(CONTENT_TYPE, multipart_type.as_str());
+                (length, Box::new(body))
+            },
+            Ranges::Full => {
+                builder.header(CONTENT_TYPE, content_type.as_str());
+                let body = storage.get(&digest, None).flatten_stream();
+                let body: ByteStream = match (passed, decoded) {
+                    (Some(encoding), _) => {
+                        Box::new(VerifyStream::decoding(body, digest, encoding.decoder()))
+                    },
+                    (_, Some((encoding, _))) => {
+                        let body = CodedStream::new(body, encoding.decoder());
+                        Box::new(VerifyStream::new(body, digest))
+                    },
+                    _ => Box::new(VerifyStream::new(body, digest)),
+                };
+                (total, body)
+            },
+        };
+        builder.header(CONTENT_LENGTH, length.to_string().as_str());
+        let body = if head { Body::empty() } else { Body::wrap_stream(body) };
+        builder.body(body).unwrap()
+    });
+    Box::new(resp)
+}
@@ -0,0 +1,248 @@
+use brotli::{CompressorWriter, DecompressorWriter};
+use flate2::Compression;
+use flate2::write::{GzDecoder, GzEncoder};
+use futures::{Async, Poll, Stream};
+use hyper::{Chunk, HeaderMap};
+use hyper::header::ACCEPT_ENCODING;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{Error, ErrorKind, Write};
+use std::mem;
+
+
+const BROTLI_BUFFER: usize = 4096;
+const BROTLI_QUALITY: u32 = 6;
+const BROTLI_WINDOW: u32 = 22;
+
+const ZSTD_LEVEL: i32 = 3;
+
+
+/// Codings files can be stored with, named as in `Content-Encoding`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Encoding {
+    #[serde(rename = "gzip")]
+    Gzip,
+    #[serde(rename = "br")]
+    Brotli,
+    #[serde(rename = "zstd")]
+    Zstd,
+}
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gzip" => Some(Encoding::Gzip),
+            "br" => Some(Encoding::Brotli),
+            "zstd" => Some(Encoding::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+        }
+    }
+
+    pub fn encoder(self) -> Coder {
+        let sink: Box<dyn Sink> = match self {
+            Encoding::Gzip => Box::new(GzEncoder::new(Vec::new(), Compression::default())),
+            Encoding::Brotli => {
+                let writer = CompressorWriter::new(
+                    Vec::new(), BROTLI_BUFFER, BROTLI_QUALITY, BROTLI_WINDOW,
+                );
+                Box::new(writer)
+            },
+            Encoding::Zstd => {
+                let encoder = zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL)
+                    .expect("zstd level is valid");
+                Box::new(encoder)
+            },
+        };
+        Coder { sink }
+    }
+
+    pub fn decoder(self) -> Coder {
+        let sink: Box<dyn Sink> = match self {
+            Encoding::Gzip => Box::new(GzDecoder::new(Vec::new())),
+            Encoding::Brotli => Box::new(DecompressorWriter::new(Vec::new(), BROTLI_BUFFER)),
+            Encoding::Zstd => {
+                let decoder = zstd::stream::write::Decoder::new(Vec::new())
+                    .expect("zstd decoder can be created");
+                Box::new(decoder)
+            },
+        };
+        Coder { sink }
+    }
+
+    /// Whether the client sending `headers` can take this coding, following
+    /// `Accept-Encoding` with its quality values.
+    pub fn is_accepted(self, headers: &HeaderMap) -> bool {
+        let mut accepted = None;
+        let mut wildcard = None;
+        let values = headers.get_all(ACCEPT_ENCODING).iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for value in values {
+            let mut params = value.split(';');
+            let coding = params.next().unwrap_or_default().trim().to_lowercase();
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .filter_map(|quality| quality.parse::<f32>().ok())
+                .next()
+                .unwrap_or(1.0);
+            if coding == self.name() {
+                accepted = Some(quality > 0.0);
+            } else if coding == "*" {
+                wildcard = Some(quality > 0.0);
+            }
+        }
+        accepted.or(wildcard).unwrap_or(false)
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+
+/// Writer that collects its coded output in a buffer.
+trait Sink: Write + Send {
+    fn output(&mut self) -> &mut Vec<u8>;
+
+    /// Ends the coding and returns the rest of the output.
+    fn finish(self: Box<Self>) -> Result<Vec<u8>, Error>;
+}
+
+impl Sink for GzEncoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vec<u8>, Error> {
+        GzEncoder::finish(*self)
+    }
+}
+
+impl Sink for GzDecoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vec<u8>, Error> {
+        GzDecoder::finish(*self)
+    }
+}
+
+impl Sink for CompressorWriter<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vec<u8>, Error> {
+        Ok(self.into_inner())
+    }
+}
+
+impl Sink for DecompressorWriter<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Vec<u8>, Error> {
+        self.close()?;
+        self.into_inner()
+            .map_err(|_| Error::new(ErrorKind::UnexpectedEof, "brotli stream is incomplete"))
+    }
+}
+
+impl Sink for zstd::stream::write::Encoder<'static, Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vec<u8>, Error> {
+        zstd::stream::write::Encoder::finish(*self)
+    }
+}
+
+impl Sink for zstd::stream::write::Decoder<'static, Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Vec<u8>, Error> {
+        self.flush()?;
+        Ok(self.into_inner())
+    }
+}
+
+
+/// Compresses or decompresses a body a chunk at a time.
+pub struct Coder {
+    sink: Box<dyn Sink>,
+}
+
+impl Coder {
+    /// Codes a chunk and returns the output that's ready, which may be empty.
+    pub fn write(&mut self, chunk: &[u8]) -> Result<Vec<u8>, Error> {
+        self.sink.write_all(chunk)?;
+        Ok(mem::take(self.sink.output()))
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, Error> {
+        self.sink.finish()
+    }
+}
+
+
+/// Passes a stream of chunks through a coder.
+pub struct CodedStream<S> {
+    inner: S,
+    coder: Option<Coder>,
+}
+
+impl<S> CodedStream<S> {
+    pub fn new(inner: S, coder: Coder) -> Self {
+        CodedStream {
+            inner,
+            coder: Some(coder),
+        }
+    }
+}
+
+impl<S> Stream for CodedStream<S>
+where
+    S: Stream<Item=Chunk, Error=Error>,
+{
+    type Item = Chunk;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, Error> {
+        loop {
+            let coder = match self.coder {
+                Some(ref mut coder) => coder,
+                None => return Ok(Async::Ready(None)),
+            };
+            match self.inner.poll()? {
+                Async::Ready(Some(chunk)) => {
+                    let output = coder.write(&chunk)?;
+                    if !output.is_empty() {
+                        return Ok(Async::Ready(Some(output.into())));
+                    }
+                },
+                Async::Ready(None) => {
+                    let output = self.coder.take().unwrap().finish()?;
+                    if !output.is_empty() {
+                        return Ok(Async::Ready(Some(output.into())));
+                    }
+                },
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use futures::{future, stream, Future, Stream};
+use hyper::{Body, Request, Response, StatusCode};
+use hyper::header::CONTENT_TYPE;
+use serde_derive::Serialize;
+use std::io::Error;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::timer::Interval;
+use crate::meta::{self, Meta};
+use crate::storage::SharedStorage;
+use crate::{empty_response, message_response, other, query_param, ResponseFuture};
+
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// How often expired files are looked for.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+
+#[derive(Serialize)]
+struct Page {
+    files: Vec<Meta>,
+    /// Passed as `after` to get the next page, `None` on the last page.
+    next: Option<String>,
+}
+
+
+/// `GET /files` lists the records of stored files ordered by id, `limit` at
+/// a time, starting after the id given as `after`.
+pub fn list(req: &Request<Body>, storage: &SharedStorage) -> ResponseFuture {
+    let limit = match query_param(req, "limit").map(str::parse::<usize>) {
+        Some(Ok(limit)) if limit > 0 && limit <= MAX_PAGE_SIZE => limit,
+        None => DEFAULT_PAGE_SIZE,
+        _ => {
+            let message = format!("limit must be between 1 and {}", MAX_PAGE_SIZE);
+            return Box::new(future::ok(message_response(StatusCode::BAD_REQUEST, message)));
+        },
+    };
+    let after = query_param(req, "after").unwrap_or_default().to_owned();
+    let storage = storage.clone();
+    let resp = meta::ids(&storage)
+        .and_then(move |ids| {
+            let mut ids = ids.into_iter().filter(|id| *id > after).peekable();
+            let page = ids.by_ref().take(limit).collect::<Vec<_>>();
+            let next = ids.peek().and(page.last().cloned());
+            let reads = page.iter()
+                .map(|id| meta::read(&storage, id))
+                .collect::<Vec<_>>();
+            future::join_all(reads).map(move |metas| {
+                let files = metas.into_iter()
+                    .flatten()
+                    .filter(|meta| !meta.is_expired())
+                    .collect();
+                Page { files, next }
+            })
+        })
+        .map(|page| {
+            Response::builder()
+                .header(CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(&page).unwrap().into())
+                .unwrap()
+        });
+    Box::new(resp)
+}
+
+
+/// Deletes a file and then its record.
+fn remove(storage: &SharedStorage, id: String) -> impl Future<Item=(), Error=Error> {
+    let storage = storage.clone();
+    storage.delete(&id).and_then(move |_| meta::remove(&storage, &id))
+}
+
+
+/// `DELETE /files/{id}` drops the reference of an owner to a file: the
+/// uploader given as `owner`, or else the caller's address. The file and its
+/// record are removed with the last reference.
+pub fn delete(req: &Request<Body>, storage: &SharedStorage, id: String, peer: SocketAddr)
+    -> ResponseFuture
+{
+    let owner = query_param(req, "owner")
+        .map(str::to_owned)
+        .unwrap_or_else(|| peer.ip().to_string());
+    let storage = storage.clone();
+    let resp = meta::read(&storage, &id).and_then(move |meta| {
+        let mut meta = match meta {
+            Some(meta) if !meta.is_expired() => meta,
+            // a file without a record has no owners to keep it
+            _ => return future::Either::A(delete_unowned(storage, id)),
+        };
+        if !meta.owners.contains(&owner) {
+            return future::Either::A(Box::new(future::ok(empty_response(StatusCode::NOT_FOUND))));
+        }
+        meta.owners.retain(|other| *other != owner);
+        let update = if meta.owners.is_empty() {
+            future::Either::A(remove(&storage, id))
+        } else {
+            future::Either::B(meta::put(&storage, &meta))
+        };
+        future::Either::B(update.map(|_| empty_response(StatusCode::NO_CONTENT)))
+    });
+    Box::new(resp)
+}
+
+
+fn delete_unowned(storage: SharedStorage, id: String) -> ResponseFuture {
+    let resp = storage.stat(&id).and_then(move |info| match info {
+        Some(_) => {
+            let remove = remove(&storage, id).map(|_| empty_response(StatusCode::NO_CONTENT));
+            future::Either::A(remove)
+        },
+        None => future::Either::B(future::ok(empty_response(StatusCode::NOT_FOUND))),
+    });
+    Box::new(resp)
+}
+
+
+/// Removes the files whose TTL has passed.
+fn sweep(storage: SharedStorage) -> impl Future<Item=(), Error=Error> {
+    meta::ids(&storage).and_then(move |ids| {
+        stream::iter_ok::<_, Error>(ids).for_each(move |id| {
+            let storage = storage.clone();
+            meta::read(&storage, &id).and_then(move |meta| match meta {
+                Some(ref meta) if meta.is_expired() => future::Either::A(remove(&storage, id)),
+                _ => future::Either::B(future::ok(())),
+            })
+        })
+    })
+}
+
+
+/// Starts removing expired files in the background, must be called on the runtime.
+pub fn spawn_sweeper(storage: SharedStorage) {
+    let sweeper = Interval::new_interval(SWEEP_INTERVAL)
+        .map_err(other)
+        .for_each(move |_| {
+            sweep(storage.clone()).or_else(|err| {
+                eprintln!("Can't remove expired files: {}", err);
+                Ok(())
+            })
+        })
+        .map_err(|err| eprintln!("Expired files are no longer removed: {}", err));
+    hyper::rt::spawn(sweeper);
+}
@@ -0,0 +1,100 @@
+use chrono::{TimeZone, Utc};
+use futures::{future, Future};
+use hmac::{Hmac, Mac};
+use hyper::{Body, Request, Response, StatusCode};
+use hyper::header::CONTENT_TYPE;
+use serde_derive::Serialize;
+use sha2::Sha256;
+use crate::config::Config;
+use crate::storage::SharedStorage;
+use crate::{empty_response, message_response, query_param, response_with_code, ResponseFuture};
+
+
+/// How long a link works unless the `ttl` query parameter says otherwise.
+const DEFAULT_LINK_TTL: i64 = 60 * 60;
+
+const MAX_LINK_TTL: i64 = 7 * 24 * 60 * 60;
+
+
+#[derive(Serialize)]
+struct Link {
+    url: String,
+    expires_at: String,
+}
+
+
+/// Signs the id of a file together with the time the link expires at,
+/// as a unix timestamp.
+fn mac(secret: &str, id: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes())
+        .expect("HMAC takes keys of any size");
+    mac.input(format!("{}\n{}", id, expires).as_bytes());
+    mac
+}
+
+
+/// `POST /files/{id}/link` responds with a signed download link, which works
+/// for `ttl` seconds.
+pub fn create(req: &Request<Body>, config: &Config, storage: &SharedStorage, id: String)
+    -> ResponseFuture
+{
+    let secret = match config.link_secret {
+        Some(ref secret) => secret.clone(),
+        None => {
+            let message = "signed links need LINK_SECRET to be set".to_owned();
+            let resp = message_response(StatusCode::NOT_IMPLEMENTED, message);
+            return Box::new(future::ok(resp));
+        },
+    };
+    let ttl = match query_param(req, "ttl") {
+        Some(ttl) => match ttl.parse::<i64>() {
+            Ok(ttl) if ttl > 0 && ttl <= MAX_LINK_TTL => ttl,
+            _ => {
+                let message = format!("ttl must be between 1 and {} seconds", MAX_LINK_TTL);
+                let resp = message_response(StatusCode::BAD_REQUEST, message);
+                return Box::new(future::ok(resp));
+            },
+        },
+        None => DEFAULT_LINK_TTL,
+    };
+    let resp = storage.stat(&id).map(move |info| {
+        if info.is_none() {
+            return empty_response(StatusCode::NOT_FOUND);
+        }
+        let expires = Utc::now().timestamp() + ttl;
+        let signature = hex::encode(mac(&secret, &id, expires).result().code());
+        let link = Link {
+            url: format!("/download/{}?expires={}&signature={}", id, expires, signature),
+            expires_at: Utc.timestamp_opt(expires, 0).unwrap().to_rfc3339(),
+        };
+        Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&link).unwrap().into())
+            .unwrap()
+    });
+    Box::new(resp)
+}
+
+
+/// Checks the signature of a download, if links are signed at all.
+///
+/// Returns the response to refuse the download with.
+pub fn verify(req: &Request<Body>, config: &Config, id: &str) -> Result<(), ResponseFuture> {
+    let secret = match config.link_secret {
+        Some(ref secret) => secret,
+        None => return Ok(()),
+    };
+    let expires = query_param(req, "expires").and_then(|expires| expires.parse::<i64>().ok());
+    let signature = query_param(req, "signature").and_then(|signature| hex::decode(signature).ok());
+    let (expires, signature) = match (expires, signature) {
+        (Some(expires), Some(signature)) => (expires, signature),
+        _ => return Err(response_with_code(StatusCode::FORBIDDEN)),
+    };
+    if mac(secret, id, expires).verify(&signature).is_err() {
+        return Err(response_with_code(StatusCode::FORBIDDEN));
+    }
+    if expires <= Utc::now().timestamp() {
+        return Err(response_with_code(StatusCode::GONE));
+    }
+    Ok(())
+}
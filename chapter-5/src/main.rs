@@ -4,18 +4,31 @@ extern crate rand;
 extern crate tokio;
 extern crate hyper_staticfile;
 
+mod admin;
+mod config;
+mod digest;
+mod download;
+mod encoding;
+mod files;
+mod link;
+mod meta;
+mod session;
+mod storage;
+mod upload;
+
 use hyper::{Body, Response, Server, Method, Request, StatusCode};
-use hyper::service::service_fn;
-use futures::{future, Stream, Future};
-use std::path::Path;
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use futures::{future, Future};
 use std::fs;
-use tokio::fs::File;
-use rand::{Rng, thread_rng};
-use rand::distributions::Alphanumeric;
+use std::net::SocketAddr;
+use std::process;
+use std::sync::Arc;
 use std::io::{Error, ErrorKind};
-use hyper_staticfile::FileChunkStream;
 use lazy_static::lazy_static;
 use regex::Regex;
+use config::Config;
+use storage::SharedStorage;
 
 
 const INDEX: &'static str = r#"
@@ -33,52 +46,91 @@ const INDEX: &'static str = r#"
 
 lazy_static! {
     static ref DOWNLOAD_FILE: Regex = 
-        Regex::new("^/download/(?P<filename>\\w{20})?$").unwrap();
+        Regex::new("^/download/(?P<digest>[0-9a-f]{64})$").unwrap();
+    static ref UPLOAD_SESSION: Regex =
+        Regex::new("^/uploads/(?P<id>\\w{20})(?P<complete>/complete)?$").unwrap();
+    static ref FILE: Regex =
+        Regex::new("^/files/(?P<id>[0-9a-f]{64})(?P<action>/meta|/link)?$").unwrap();
 }
 
 
-fn microservice_handler(req: Request<Body>, files: &Path)
-    -> Box<dyn Future<Item=Response<Body>, Error=std::io::Error> + Send> 
-{
+type ResponseFuture = Box<dyn Future<Item=Response<Body>, Error=Error> + Send>;
+
+
+fn microservice_handler(
+    req: Request<Body>,
+    config: &Config,
+    storage: &SharedStorage,
+    peer: SocketAddr,
+) -> ResponseFuture {
+    let files = &config.files;
     match (req.method(), req.uri().path().to_owned().as_ref()) {
         (&Method::GET, "/") => {
             Box::new(future::ok(Response::new(INDEX.into())))
         },
-        (&Method::GET, path) if path.starts_with("/download") => {
+        (&Method::GET, path) | (&Method::HEAD, path) if path.starts_with("/download") => {
             if let Some(cap) = DOWNLOAD_FILE.captures(path) {
-                let filename = cap.name("filename").unwrap().as_str();
-                let mut filepath = files.to_path_buf();
-                filepath.push(filename);
-                let open_file = File::open(filepath);
-                let body = open_file.map(|file| {
-                    let chunks = FileChunkStream::new(file);
-                    Response::new(Body::wrap_stream(chunks))
+                let digest = cap.name("digest").unwrap().as_str().to_owned();
+                if let Err(resp) = link::verify(&req, config, &digest) {
+                    return resp;
+                }
+                let storage = storage.clone();
+                let resp = meta::read(&storage, &digest).and_then(move |meta| match meta {
+                    Some(ref meta) if meta.is_expired() => {
+                        future::Either::A(future::ok(empty_response(StatusCode::NOT_FOUND)))
+                    },
+                    meta => future::Either::B(download::serve(&req, &storage, digest, meta)),
                 });
-                Box::new(body)
+                Box::new(resp)
             } else {
                 response_with_code(StatusCode::NOT_FOUND)
             }
         },
         (&Method::POST, "/upload") => {
-            let name: String = thread_rng()
-                .sample_iter(&Alphanumeric)
-                .take(20)
-                .collect();
-            let mut filepath = files.to_path_buf();
-            filepath.push(&name);
-            let create_file = File::create(filepath);
-            let write = create_file.and_then(|file| {
-                req.into_body()
-                    .map_err(other)
-                    .fold(file, |file, chunk| {
-                        tokio::io::write_all(file, chunk)
-                            .map(|(file, _)| file)
-                    })
-            });
-            let body = write.map(|_| {
-                Response::new(name.into())
-            });
-            Box::new(body)
+            upload::upload(req, config, storage, peer)
+        },
+        (&Method::GET, "/files") => {
+            if let Err(resp) = admin::authorize(&req, config) {
+                return resp;
+            }
+            files::list(&req, storage)
+        },
+        (method, path) if path.starts_with("/files/") => {
+            if let Some(cap) = FILE.captures(path) {
+                let id = cap.name("id").unwrap().as_str().to_owned();
+                let action = cap.name("action").map(|action| action.as_str());
+                if let (&Method::POST, Some("/link")) | (&Method::DELETE, None) = (method, action) {
+                    if let Err(resp) = admin::authorize(&req, config) {
+                        return resp;
+                    }
+                }
+                match (method.clone(), action) {
+                    (Method::GET, Some("/meta")) => meta::serve(storage, &id),
+                    (Method::POST, Some("/link")) => link::create(&req, config, storage, id),
+                    (Method::DELETE, None) => files::delete(&req, storage, id, peer),
+                    _ => response_with_code(StatusCode::METHOD_NOT_ALLOWED),
+                }
+            } else {
+                response_with_code(StatusCode::NOT_FOUND)
+            }
+        },
+        (&Method::POST, "/uploads") => {
+            session::create(&req, config, peer)
+        },
+        (method, path) if path.starts_with("/uploads/") => {
+            if let Some(cap) = UPLOAD_SESSION.captures(path) {
+                let id = cap.name("id").unwrap().as_str().to_owned();
+                let complete = cap.name("complete").is_some();
+                match (method.clone(), complete) {
+                    (Method::HEAD, false) => session::offset(files, &id),
+                    (Method::PATCH, false) => session::append(req, config, id),
+                    (Method::DELETE, false) => session::cancel(files, &id),
+                    (Method::POST, true) => session::complete(&req, config, storage, id),
+                    _ => response_with_code(StatusCode::METHOD_NOT_ALLOWED),
+                }
+            } else {
+                response_with_code(StatusCode::NOT_FOUND)
+            }
         },
         _ => {
             response_with_code(StatusCode::NOT_FOUND)
@@ -95,25 +147,66 @@ where
 }
 
 
-fn response_with_code(status_code: StatusCode) 
-    -> Box<dyn Future<Item=Response<Body>, Error=Error> + Send>
-{
-    let resp = Response::builder()
+fn empty_response(status_code: StatusCode) -> Response<Body> {
+    Response::builder()
         .status(status_code)
         .body(Body::empty())
-        .unwrap();
-    Box::new(future::ok(resp))
+        .unwrap()
+}
+
+
+fn message_response(status_code: StatusCode, message: String) -> Response<Body> {
+    Response::builder()
+        .status(status_code)
+        .body(message.into())
+        .unwrap()
+}
+
+
+fn response_with_code(status_code: StatusCode) -> ResponseFuture {
+    Box::new(future::ok(empty_response(status_code)))
+}
+
+
+/// Value of a query parameter, which isn't percent-decoded.
+fn query_param<'a>(req: &'a Request<Body>, name: &str) -> Option<&'a str> {
+    req.uri().query()?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
 }
 
 
 fn main() {
-    let files = Path::new("./files");
-    fs::create_dir(files).ok();
+    let config = match Config::from_env() {
+        Ok(config) => Arc::new(config),
+        Err(err) => {
+            eprintln!("Can't load config: {}", err);
+            process::exit(1);
+        },
+    };
+    let storage = match storage::open(&config) {
+        Ok(storage) => storage,
+        Err(err) => {
+            eprintln!("Can't open storage: {}", err);
+            process::exit(1);
+        },
+    };
+    fs::create_dir(&config.files).ok();
+    session::spawn_sweeper(config.files.clone());
+    let sweeper_storage = storage.clone();
     let addr = ([127, 0, 0, 1], 8080).into();
     let builder = Server::bind(&addr);
-    let server = builder.serve(move || {
-        service_fn(move |req| microservice_handler(req, &files))
-    });
+    let server = builder.serve(make_service_fn(move |conn: &AddrStream| {
+        let peer = conn.remote_addr();
+        let config = config.clone();
+        let storage = storage.clone();
+        service_fn(move |req| microservice_handler(req, &config, &storage, peer))
+    }));
     let server = server.map_err(drop);
-    hyper::rt::run(server);
+    hyper::rt::run(future::lazy(move || {
+        files::spawn_sweeper(sweeper_storage);
+        server
+    }));
 }
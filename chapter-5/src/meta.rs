@@ -0,0 +1,216 @@
+use chrono::{DateTime, Duration, Utc};
+use futures::{future, stream, Future, Stream};
+use hyper::{Body, Chunk, Request, Response, StatusCode};
+use hyper::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use serde_derive::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+use std::net::SocketAddr;
+use crate::encoding::Encoding;
+use crate::storage::SharedStorage;
+use crate::{empty_response, query_param, ResponseFuture};
+
+
+/// Metadata records are kept in the storage next to the files, one JSON
+/// document each.
+const META_PREFIX: &str = "meta/";
+
+
+/// Where an upload comes from, known when the upload starts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Origin {
+    /// Name of the file on the client, from `Content-Disposition`.
+    pub filename: Option<String>,
+    /// Address of the client that sent the upload.
+    pub uploader: String,
+    /// When the file is deleted, from the `ttl` query parameter in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Origin {
+    pub fn from_request(req: &Request<Body>, peer: SocketAddr) -> Result<Self, String> {
+        let filename = req.headers().get(CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(disposition_filename);
+        let expires_at = match query_param(req, "ttl") {
+            Some(ttl) => match ttl.parse::<u32>() {
+                Ok(ttl) if ttl > 0 => Some(Utc::now() + Duration::seconds(ttl.into())),
+                _ => return Err(format!("invalid ttl {}", ttl)),
+            },
+            None => None,
+        };
+        Ok(Origin {
+            filename,
+            uploader: peer.ip().to_string(),
+            expires_at,
+        })
+    }
+
+    /// Whether a file uploaded from here is kept longer than from `other`.
+    fn outlives(&self, other: &Origin) -> bool {
+        match (self.expires_at, other.expires_at) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(expires_at), Some(other)) => expires_at > other,
+        }
+    }
+}
+
+
+/// Reads `filename` of a `Content-Disposition` header, without any path.
+fn disposition_filename(value: &str) -> Option<String> {
+    let filename = value.split(';')
+        .filter_map(|param| param.trim().strip_prefix("filename="))
+        .next()?
+        .trim_matches('"');
+    let filename = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+    if filename.is_empty() {
+        None
+    } else {
+        Some(filename.to_owned())
+    }
+}
+
+
+/// Record kept for every stored file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Meta {
+    /// The digest the file is stored under.
+    pub id: String,
+    #[serde(flatten)]
+    pub origin: Origin,
+    /// Media type sniffed from the content.
+    pub content_type: String,
+    /// Size of the content, before any compression.
+    pub size: u64,
+    /// Coding the file is stored with, downloads are decoded for clients
+    /// that don't accept it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<Encoding>,
+    pub uploaded_at: DateTime<Utc>,
+    /// Uploaders referencing the content, the file is removed once the last
+    /// of them deletes it. Records from before have their uploader only.
+    #[serde(default)]
+    pub owners: Vec<String>,
+}
+
+impl Meta {
+    /// An expired file is treated as gone, even before the sweeper removes it.
+    pub fn is_expired(&self) -> bool {
+        self.origin.expires_at.is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+
+fn meta_key(id: &str) -> String {
+    format!("{}{}.json", META_PREFIX, id)
+}
+
+
+/// Saves the record of a file, over the one it has.
+pub fn put(storage: &SharedStorage, meta: &Meta) -> impl Future<Item=(), Error=Error> {
+    let data = serde_json::to_vec_pretty(meta).expect("metadata is always serializable");
+    let size = data.len() as u64;
+    let body = stream::once(Ok(Chunk::from(data)));
+    storage.put(&meta_key(&meta.id), Box::new(body), size)
+}
+
+
+/// Saves the record of a file, unless it already has one.
+///
+/// Content is deduplicated, so the first upload of a file keeps its record.
+/// Later uploaders are added to the owners, and the expiry is taken from
+/// them if they want the file kept for longer. The stored object is kept
+/// too, and with it its coding.
+pub fn write(storage: &SharedStorage, mut meta: Meta) -> impl Future<Item=(), Error=Error> {
+    let storage = storage.clone();
+    read(&storage, &meta.id).and_then(move |old| {
+        let meta = match old {
+            Some(mut old) if !old.is_expired() => {
+                let uploader = &meta.origin.uploader;
+                let new_owner = !old.owners.contains(uploader);
+                let outlives = meta.origin.outlives(&old.origin);
+                if !new_owner && !outlives {
+                    return future::Either::A(future::ok(()));
+                }
+                if new_owner {
+                    old.owners.push(uploader.clone());
+                }
+                if outlives {
+                    old.origin.expires_at = meta.origin.expires_at;
+                }
+                old
+            },
+            Some(old) => {
+                meta.encoding = old.encoding;
+                meta
+            },
+            None => meta,
+        };
+        future::Either::B(put(&storage, &meta))
+    })
+}
+
+
+/// Removes the record of a file.
+pub fn remove(storage: &SharedStorage, id: &str) -> impl Future<Item=(), Error=Error> {
+    storage.delete(&meta_key(id))
+}
+
+
+/// Ids of all files with a record, in order.
+pub fn ids(storage: &SharedStorage) -> impl Future<Item=Vec<String>, Error=Error> {
+    storage.list(META_PREFIX).map(|infos| {
+        let mut ids = infos.into_iter()
+            .filter_map(|info| {
+                info.key.strip_prefix(META_PREFIX)?
+                    .strip_suffix(".json")
+                    .map(str::to_owned)
+            })
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    })
+}
+
+
+/// Loads the record of a file, `None` if it has none.
+pub fn read(storage: &SharedStorage, id: &str) -> impl Future<Item=Option<Meta>, Error=Error> {
+    storage.get(&meta_key(id), None)
+        .and_then(|body| body.concat2())
+        .then(|res| match res {
+            Ok(data) => serde_json::from_slice::<Meta>(&data)
+                .map(|mut meta| {
+                    if meta.owners.is_empty() {
+                        meta.owners.push(meta.origin.uploader.clone());
+                    }
+                    Some(meta)
+                })
+                .map_err(|err| Error::new(ErrorKind::InvalidData, err)),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        })
+}
+
+
+/// `GET /files/{id}/meta` responds with the record of a file as JSON.
+pub fn serve(storage: &SharedStorage, id: &str) -> ResponseFuture {
+    let resp = read(storage, id).map(|meta| match meta {
+        Some(meta) if !meta.is_expired() => {
+            Response::builder()
+                .header(CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(&meta).unwrap().into())
+                .unwrap()
+        },
+        _ => empty_response(StatusCode::NOT_FOUND),
+    });
+    Box::new(resp)
+}
+
+
+/// The media type downloads of a file are served with.
+pub fn content_type(meta: &Option<Meta>) -> String {
+    meta.as_ref()
+        .map(|meta| meta.content_type.clone())
+        .unwrap_or_else(|| "application/octet-stream".to_owned())
+}
@@ -0,0 +1,406 @@
+use futures::{future, Future, Stream};
+use hyper::{Body, Request, Response, StatusCode};
+use hyper::header::{CACHE_CONTROL, CONTENT_TYPE, LOCATION};
+use hyper_staticfile::FileChunkStream;
+use lazy_static::lazy_static;
+use rand::{Rng, thread_rng};
+use rand::distributions::Alphanumeric;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tokio::fs::{File, OpenOptions};
+use crate::config::Config;
+use crate::meta::Origin;
+use crate::storage::SharedStorage;
+use crate::upload::{self, Inspector, Rejected};
+use crate::{digest, empty_response, message_response, other, ResponseFuture};
+
+
+/// Sessions that haven't received data for this long are removed.
+const SESSION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+const SESSIONS_DIR: &str = ".uploads";
+
+const UPLOAD_OFFSET: &str = "upload-offset";
+const UPLOAD_LENGTH: &str = "upload-length";
+const UPLOAD_CHECKSUM: &str = "upload-checksum";
+
+const OFFSET_OCTETS: &str = "application/offset+octet-stream";
+
+
+lazy_static! {
+    /// Sessions a request is writing to or completing right now.
+    static ref BUSY: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+
+/// Marks a session as busy until it's dropped.
+struct Busy(String);
+
+impl Busy {
+    fn acquire(id: &str) -> Option<Self> {
+        if BUSY.lock().unwrap().insert(id.to_owned()) {
+            Some(Busy(id.to_owned()))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        BUSY.lock().unwrap().remove(&self.0);
+    }
+}
+
+
+/// A session is a directory with the `data` received so far, the `origin`
+/// of the upload and, if the client announced it, the expected `length`.
+///
+/// Sessions are kept on the local disk until they are completed, so all
+/// requests of a session have to reach the same replica.
+fn session_dir(files: &Path, id: &str) -> PathBuf {
+    files.join(SESSIONS_DIR).join(id)
+}
+
+
+/// Returns the current offset and the expected length of a session.
+fn session_state(dir: &Path) -> impl Future<Item=(u64, Option<u64>), Error=Error> {
+    let offset = tokio::fs::metadata(dir.join("data")).map(|meta| meta.len());
+    let length = tokio::fs::read(dir.join("length")).then(|res| match res {
+        Ok(data) => {
+            String::from_utf8(data).ok()
+                .and_then(|length| length.parse().ok())
+                .map(Some)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "corrupted upload length"))
+        },
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    });
+    offset.join(length)
+}
+
+
+fn session_origin(dir: &Path) -> impl Future<Item=Origin, Error=Error> {
+    tokio::fs::read(dir.join("origin")).and_then(|data| {
+        serde_json::from_slice(&data).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    })
+}
+
+
+fn remove_session(dir: PathBuf) -> impl Future<Item=(), Error=Error> {
+    let removals = ["data", "length", "origin"].iter()
+        .map(|name| {
+            tokio::fs::remove_file(dir.join(name)).then(|res| match res {
+                Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(()),
+                res => res,
+            })
+        })
+        .collect::<Vec<_>>();
+    future::join_all(removals).and_then(move |_| tokio::fs::remove_dir(dir))
+}
+
+
+fn header_u64(req: &Request<Body>, name: &str) -> Result<Option<u64>, String> {
+    req.headers().get(name)
+        .map(|value| {
+            value.to_str().ok()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| format!("invalid {} header", name))
+        })
+        .transpose()
+}
+
+
+fn bad_request(message: String) -> ResponseFuture {
+    Box::new(future::ok(message_response(StatusCode::BAD_REQUEST, message)))
+}
+
+
+fn busy_response() -> ResponseFuture {
+    let message = "another request is using the upload".to_owned();
+    Box::new(future::ok(message_response(StatusCode::CONFLICT, message)))
+}
+
+
+fn session_error(err: Error) -> Result<Response<Body>, Error> {
+    if err.kind() == ErrorKind::NotFound {
+        Ok(empty_response(StatusCode::NOT_FOUND))
+    } else {
+        Err(err)
+    }
+}
+
+
+/// `POST /uploads` starts a session, `Upload-Length` may announce the size.
+pub fn create(req: &Request<Body>, config: &Config, peer: SocketAddr) -> ResponseFuture {
+    let length = match header_u64(req, UPLOAD_LENGTH) {
+        Ok(length) => length,
+        Err(message) => return bad_request(message),
+    };
+    if length.is_some_and(|length| length > config.max_size) {
+        let err = upload::too_large(config.max_size);
+        return Box::new(future::result(upload::rejection_response(err)));
+    }
+    let origin = match Origin::from_request(req, peer) {
+        Ok(origin) => serde_json::to_vec(&origin).unwrap(),
+        Err(message) => return bad_request(message),
+    };
+    let id: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(20)
+        .collect();
+    let dir = session_dir(&config.files, &id);
+    let data = dir.join("data");
+    let origin_path = dir.join("origin");
+    let create = tokio::fs::create_dir_all(dir.clone())
+        .and_then(move |_| tokio::fs::write(origin_path, origin))
+        .and_then(move |_| match length {
+            Some(length) => {
+                let write = tokio::fs::write(dir.join("length"), length.to_string().into_bytes());
+                future::Either::A(write.map(drop))
+            },
+            None => future::Either::B(future::ok(())),
+        })
+        .and_then(move |_| File::create(data))
+        .map(move |_| {
+            Response::builder()
+                .status(StatusCode::CREATED)
+                .header(LOCATION, format!("/uploads/{}", id).as_str())
+                .header(UPLOAD_OFFSET, "0")
+                .body(Body::empty())
+                .unwrap()
+        });
+    Box::new(create)
+}
+
+
+/// `HEAD /uploads/{id}` reports how much of the upload has been received.
+pub fn offset(files: &Path, id: &str) -> ResponseFuture {
+    let resp = session_state(&session_dir(files, id)).then(|res| {
+        let (offset, length) = match res {
+            Ok(state) => state,
+            Err(err) => return session_error(err),
+        };
+        let mut builder = Response::builder();
+        builder
+            .header(UPLOAD_OFFSET, offset.to_string().as_str())
+            .header(CACHE_CONTROL, "no-store");
+        if let Some(length) = length {
+            builder.header(UPLOAD_LENGTH, length.to_string().as_str());
+        }
+        Ok(builder.body(Body::empty()).unwrap())
+    });
+    Box::new(resp)
+}
+
+
+/// `PATCH /uploads/{id}` appends the body at `Upload-Offset`, which has to
+/// be the current offset of the session.
+///
+/// The bytes received before a connection breaks are kept, so the client can
+/// ask for the offset and continue from there.
+pub fn append(req: Request<Body>, config: &Config, id: String) -> ResponseFuture {
+    if req.headers().get(CONTENT_TYPE).is_none_or(|value| value != OFFSET_OCTETS) {
+        let message = format!("expected {} content", OFFSET_OCTETS);
+        let resp = message_response(StatusCode::UNSUPPORTED_MEDIA_TYPE, message);
+        return Box::new(future::ok(resp));
+    }
+    let offset = match header_u64(&req, UPLOAD_OFFSET) {
+        Ok(Some(offset)) => offset,
+        Ok(None) => return bad_request(format!("missing {} header", UPLOAD_OFFSET)),
+        Err(message) => return bad_request(message),
+    };
+    let busy = match Busy::acquire(&id) {
+        Some(busy) => busy,
+        None => return busy_response(),
+    };
+    let max_size = config.max_size;
+    let dir = session_dir(&config.files, &id);
+    let data = dir.join("data");
+    let resp = session_state(&dir).then(move |res| -> ResponseFuture {
+        let (current, length) = match res {
+            Ok(state) => state,
+            Err(err) => return Box::new(future::result(session_error(err))),
+        };
+        if offset != current {
+            let resp = Response::builder()
+                .status(StatusCode::CONFLICT)
+                .header(UPLOAD_OFFSET, current.to_string().as_str())
+                .body(Body::empty())
+                .unwrap();
+            return Box::new(future::ok(resp));
+        }
+        let write = OpenOptions::new().append(true).open(data).and_then(move |file| {
+            req.into_body()
+                .map_err(other)
+                .fold((file, 0), move |(file, written), chunk| {
+                    let written = written + chunk.len() as u64;
+                    let end = current + written;
+                    if end > max_size {
+                        return future::Either::A(future::err(upload::too_large(max_size)));
+                    }
+                    if length.is_some_and(|length| end > length) {
+                        let message = format!("upload is longer than {} bytes", length.unwrap());
+                        let err = Rejected::error(StatusCode::PAYLOAD_TOO_LARGE, message);
+                        return future::Either::A(future::err(err));
+                    }
+                    let write = tokio::io::write_all(file, chunk)
+                        .map(move |(file, _)| (file, written));
+                    future::Either::B(write)
+                })
+        });
+        let resp = write.then(move |res| {
+            drop(busy);
+            match res {
+                Ok((_, written)) => {
+                    let resp = Response::builder()
+                        .status(StatusCode::NO_CONTENT)
+                        .header(UPLOAD_OFFSET, (offset + written).to_string().as_str())
+                        .body(Body::empty())
+                        .unwrap();
+                    Ok(resp)
+                },
+                Err(err) => upload::rejection_response(err),
+            }
+        });
+        Box::new(resp)
+    });
+    Box::new(resp)
+}
+
+
+/// `POST /uploads/{id}/complete` checks the received data against
+/// `Upload-Checksum: sha256 <hex digest>` and the allowed types, and stores
+/// it like a single request upload.
+///
+/// If the data is refused the session is kept, so the client may cancel it
+/// or let it expire.
+pub fn complete(req: &Request<Body>, config: &Config, storage: &SharedStorage, id: String)
+    -> ResponseFuture
+{
+    let checksum = req.headers().get(UPLOAD_CHECKSUM)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("sha256 "))
+        .map(|digest| digest.trim().to_lowercase());
+    let checksum = match checksum {
+        Some(checksum) => checksum,
+        None => return bad_request(format!("expected {}: sha256 <hex digest>", UPLOAD_CHECKSUM)),
+    };
+    let busy = match Busy::acquire(&id) {
+        Some(busy) => busy,
+        None => return busy_response(),
+    };
+    let config = config.clone();
+    let storage = storage.clone();
+    let inspector = Inspector::new(&config);
+    let dir = session_dir(&config.files, &id);
+    let data = dir.join("data");
+    let resp = session_state(&dir).then(move |res| -> ResponseFuture {
+        let (offset, length) = match res {
+            Ok(state) => state,
+            Err(err) => return Box::new(future::result(session_error(err))),
+        };
+        if let Some(length) = length.filter(|length| *length != offset) {
+            let message = format!("received {} of {} bytes", offset, length);
+            return Box::new(future::ok(message_response(StatusCode::CONFLICT, message)));
+        }
+        let read = File::open(data.clone()).and_then(|file| {
+            FileChunkStream::new(file)
+                .fold((Sha256::new(), inspector), |(mut hasher, mut inspector), chunk| {
+                    inspector.inspect(&chunk)?;
+                    hasher.input(&chunk);
+                    Ok::<_, Error>((hasher, inspector))
+                })
+        });
+        let resp = read
+            .join(session_origin(&dir))
+            .and_then(move |((hasher, inspector), origin)| {
+                let _busy = busy;
+                let digest = digest::to_hex(hasher.clone());
+                if digest != checksum {
+                    let message = format!("received data has digest {}", digest);
+                    let resp = message_response(StatusCode::UNPROCESSABLE_ENTITY, message);
+                    return future::Either::A(future::ok(resp));
+                }
+                let store = future::result(inspector.finish())
+                    .and_then(move |(size, content_type)| {
+                        upload::store(data, &config, &storage, hasher, size, content_type, origin)
+                    })
+                    .and_then(move |resp| remove_session(dir).map(move |_| resp));
+                future::Either::B(store)
+            })
+            .or_else(upload::rejection_response);
+        Box::new(resp)
+    });
+    Box::new(resp)
+}
+
+
+/// `DELETE /uploads/{id}` drops a session and its data.
+pub fn cancel(files: &Path, id: &str) -> ResponseFuture {
+    let busy = match Busy::acquire(id) {
+        Some(busy) => busy,
+        None => return busy_response(),
+    };
+    let dir = session_dir(files, id);
+    let resp = session_state(&dir)
+        .and_then(move |_| remove_session(dir))
+        .then(move |res| {
+            drop(busy);
+            match res {
+                Ok(()) => Ok(empty_response(StatusCode::NO_CONTENT)),
+                Err(err) => session_error(err),
+            }
+        });
+    Box::new(resp)
+}
+
+
+/// Removes sessions that haven't received data for `SESSION_TTL`, and the
+/// parts of single request uploads left behind when the service stopped.
+pub fn spawn_sweeper(files: PathBuf) {
+    thread::spawn(move || loop {
+        sweep(&files);
+        thread::sleep(SWEEP_INTERVAL);
+    });
+}
+
+
+fn sweep(files: &Path) {
+    let now = SystemTime::now();
+    let is_stale = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .map(|modified| now.duration_since(modified).unwrap_or_default() > SESSION_TTL)
+            .unwrap_or(false)
+    };
+    if let Ok(entries) = fs::read_dir(files.join(SESSIONS_DIR)) {
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            let id = entry.file_name().to_string_lossy().into_owned();
+            // sessions without data are judged by the age of their directory
+            let data = dir.join("data");
+            let stale = if data.exists() { is_stale(&data) } else { is_stale(&dir) };
+            if let (true, Some(_busy)) = (stale, Busy::acquire(&id)) {
+                fs::remove_dir_all(&dir).ok();
+            }
+        }
+    }
+    if let Ok(entries) = fs::read_dir(files) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') && name.ends_with(".part") && is_stale(&entry.path()) {
+                fs::remove_file(entry.path()).ok();
+            }
+        }
+    }
+}
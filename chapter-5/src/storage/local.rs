@@ -0,0 +1,168 @@
+use futures::{future, Future, Stream};
+use hyper_staticfile::FileChunkStream;
+use rand::{Rng, thread_rng};
+use rand::distributions::Alphanumeric;
+use std::io::{Error, ErrorKind, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::fs::File;
+use super::{ByteRange, ByteStream, Limit, ObjectInfo, Storage, StorageFuture};
+
+
+/// Keeps objects as files under a directory.
+///
+/// Names starting with a dot are reserved for files that are still being
+/// written, they are never listed.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        LocalStorage { root }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, Error> {
+        let valid = Path::new(key).components().all(|component| match component {
+            Component::Normal(name) => !name.to_string_lossy().starts_with('.'),
+            _ => false,
+        });
+        if valid {
+            Ok(self.root.join(key))
+        } else {
+            Err(Error::new(ErrorKind::InvalidInput, format!("invalid key {}", key)))
+        }
+    }
+}
+
+fn ignore_missing(res: Result<(), Error>) -> Result<(), Error> {
+    match res {
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put(&self, key: &str, body: ByteStream, _size: u64) -> StorageFuture<()> {
+        let path = match self.path(key) {
+            Ok(path) => path,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let dir = path.parent().unwrap().to_path_buf();
+        let name: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(20)
+            .collect();
+        let temp = dir.join(format!(".{}.tmp", name));
+        let cleanup = temp.clone();
+        let write = tokio::fs::create_dir_all(dir)
+            .and_then({
+                let temp = temp.clone();
+                move |_| File::create(temp)
+            })
+            .and_then(move |file| {
+                body.fold(file, |file, chunk| {
+                    tokio::io::write_all(file, chunk).map(|(file, _)| file)
+                })
+            })
+            .and_then(move |_| tokio::fs::rename(temp, path))
+            .or_else(move |err| tokio::fs::remove_file(cleanup).then(|_| Err(err)));
+        Box::new(write)
+    }
+
+    fn get(&self, key: &str, range: Option<ByteRange>) -> StorageFuture<ByteStream> {
+        let path = match self.path(key) {
+            Ok(path) => path,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let open = File::open(path);
+        match range {
+            Some(range) => {
+                let stream = open
+                    .and_then(move |file| file.seek(SeekFrom::Start(range.start)))
+                    .map(move |(file, _)| -> ByteStream {
+                        Box::new(Limit::new(FileChunkStream::new(file), range.len()))
+                    });
+                Box::new(stream)
+            },
+            None => Box::new(open.map(|file| -> ByteStream {
+                Box::new(FileChunkStream::new(file))
+            })),
+        }
+    }
+
+    fn delete(&self, key: &str) -> StorageFuture<()> {
+        match self.path(key) {
+            Ok(path) => Box::new(tokio::fs::remove_file(path).then(ignore_missing)),
+            Err(err) => Box::new(future::err(err)),
+        }
+    }
+
+    fn stat(&self, key: &str) -> StorageFuture<Option<ObjectInfo>> {
+        let path = match self.path(key) {
+            Ok(path) => path,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let key = key.to_owned();
+        let stat = tokio::fs::metadata(path).then(move |res| match res {
+            Ok(meta) if meta.is_file() => {
+                Ok(Some(ObjectInfo {
+                    key,
+                    size: meta.len(),
+                    modified: meta.modified().unwrap_or(UNIX_EPOCH),
+                }))
+            },
+            Ok(_) => Ok(None),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        });
+        Box::new(stat)
+    }
+
+    fn list(&self, prefix: &str) -> StorageFuture<Vec<ObjectInfo>> {
+        let dir = self.root.join(prefix);
+        let prefix = prefix.to_owned();
+        let list = tokio::fs::read_dir(dir)
+            .flatten_stream()
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .and_then(|entry| {
+                let name = entry.file_name();
+                future::poll_fn(move || entry.poll_metadata()).map(move |meta| (name, meta))
+            })
+            .filter(|(_, meta)| meta.is_file())
+            .map(move |(name, meta)| ObjectInfo {
+                key: format!("{}{}", prefix, name.to_string_lossy()),
+                size: meta.len(),
+                modified: meta.modified().unwrap_or(UNIX_EPOCH),
+            })
+            .collect()
+            .then(|res| match res {
+                Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+                res => res,
+            });
+        Box::new(list)
+    }
+
+    /// Moves the file into place, which is cheap on the same file system.
+    fn put_file(&self, key: &str, path: PathBuf, size: u64) -> StorageFuture<()> {
+        let target = match self.path(key) {
+            Ok(target) => target,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let dir = target.parent().unwrap().to_path_buf();
+        let key = key.to_owned();
+        let copy = LocalStorage::new(self.root.clone());
+        let rename = tokio::fs::create_dir_all(dir)
+            .and_then({
+                let path = path.clone();
+                move |_| tokio::fs::rename(path, target)
+            })
+            .or_else(move |_| {
+                let body = File::open(path)
+                    .map(FileChunkStream::new)
+                    .flatten_stream();
+                copy.put(&key, Box::new(body), size)
+            });
+        Box::new(rename)
+    }
+}
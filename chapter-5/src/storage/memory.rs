@@ -0,0 +1,97 @@
+use futures::{future, stream, Future, Stream};
+use hyper::Chunk;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use super::{not_found, ByteRange, ByteStream, ObjectInfo, Storage, StorageFuture};
+
+
+struct Object {
+    data: Vec<u8>,
+    modified: SystemTime,
+}
+
+
+/// Keeps objects in memory, they are lost when the service stops.
+#[derive(Default)]
+pub struct MemoryStorage {
+    objects: Arc<Mutex<HashMap<String, Object>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn put(&self, key: &str, body: ByteStream, size: u64) -> StorageFuture<()> {
+        let objects = self.objects.clone();
+        let key = key.to_owned();
+        let put = body.concat2().and_then(move |data| {
+            if data.len() as u64 != size {
+                let msg = format!("expected {} bytes, got {}", size, data.len());
+                return Err(Error::new(ErrorKind::UnexpectedEof, msg));
+            }
+            let object = Object {
+                data: data.to_vec(),
+                modified: SystemTime::now(),
+            };
+            objects.lock().unwrap().insert(key, object);
+            Ok(())
+        });
+        Box::new(put)
+    }
+
+    fn get(&self, key: &str, range: Option<ByteRange>) -> StorageFuture<ByteStream> {
+        let objects = self.objects.lock().unwrap();
+        let data = match objects.get(key) {
+            Some(object) => &object.data,
+            None => return Box::new(future::err(not_found(key))),
+        };
+        let data = match range {
+            Some(range) => {
+                // like a file, bytes past the end just aren't there
+                let len = data.len() as u64;
+                let start = range.start.min(len);
+                let end = range.end.saturating_add(1).clamp(start, len);
+                data[start as usize..end as usize].to_vec()
+            },
+            None => data.clone(),
+        };
+        let body: ByteStream = Box::new(stream::once(Ok(Chunk::from(data))));
+        Box::new(future::ok(body))
+    }
+
+    fn delete(&self, key: &str) -> StorageFuture<()> {
+        self.objects.lock().unwrap().remove(key);
+        Box::new(future::ok(()))
+    }
+
+    fn stat(&self, key: &str) -> StorageFuture<Option<ObjectInfo>> {
+        let info = self.objects.lock().unwrap()
+            .get(key)
+            .map(|object| ObjectInfo {
+                key: key.to_owned(),
+                size: object.data.len() as u64,
+                modified: object.modified,
+            });
+        Box::new(future::ok(info))
+    }
+
+    fn list(&self, prefix: &str) -> StorageFuture<Vec<ObjectInfo>> {
+        let infos = self.objects.lock().unwrap()
+            .iter()
+            .filter(|(key, _)| {
+                key.strip_prefix(prefix).is_some_and(|name| !name.contains('/'))
+            })
+            .map(|(key, object)| ObjectInfo {
+                key: key.clone(),
+                size: object.data.len() as u64,
+                modified: object.modified,
+            })
+            .collect();
+        Box::new(future::ok(infos))
+    }
+}
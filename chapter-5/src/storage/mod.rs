@@ -0,0 +1,174 @@
+mod local;
+mod memory;
+mod s3;
+
+pub use self::local::LocalStorage;
+pub use self::memory::MemoryStorage;
+pub use self::s3::{S3Config, S3Storage};
+
+use futures::{Async, Future, Poll, Stream};
+use hyper::Chunk;
+use hyper_staticfile::FileChunkStream;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::fs::File;
+use crate::config::{Config, StorageConfig};
+
+
+pub type ByteStream = Box<dyn Stream<Item=Chunk, Error=Error> + Send>;
+
+pub type StorageFuture<T> = Box<dyn Future<Item=T, Error=Error> + Send>;
+
+pub type SharedStorage = Arc<dyn Storage>;
+
+
+/// Inclusive range of bytes of an object.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+
+#[derive(Clone, Debug)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+
+/// Where stored files and their metadata live.
+///
+/// Keys are `/` separated paths. Getting a missing object fails with
+/// `ErrorKind::NotFound`, deleting one succeeds.
+pub trait Storage: Send + Sync + 'static {
+    /// Stores `size` bytes from `body` under `key`, replacing any object
+    /// there. Readers never see a partly written object.
+    fn put(&self, key: &str, body: ByteStream, size: u64) -> StorageFuture<()>;
+
+    /// Streams an object, or only `range` of it.
+    fn get(&self, key: &str, range: Option<ByteRange>) -> StorageFuture<ByteStream>;
+
+    fn delete(&self, key: &str) -> StorageFuture<()>;
+
+    /// Returns `None` if there is no object under `key`.
+    fn stat(&self, key: &str) -> StorageFuture<Option<ObjectInfo>>;
+
+    /// Lists the objects directly under `prefix`, which is empty or ends with `/`.
+    fn list(&self, prefix: &str) -> StorageFuture<Vec<ObjectInfo>>;
+
+    /// Stores a local file under `key`; the file may be moved or left in place.
+    fn put_file(&self, key: &str, path: PathBuf, size: u64) -> StorageFuture<()> {
+        let body = File::open(path)
+            .map(FileChunkStream::new)
+            .flatten_stream();
+        self.put(key, Box::new(body), size)
+    }
+}
+
+
+/// Opens the backend chosen in the config.
+pub fn open(config: &Config) -> Result<SharedStorage, String> {
+    let storage: SharedStorage = match config.storage {
+        StorageConfig::Local => Arc::new(LocalStorage::new(config.files.clone())),
+        StorageConfig::Memory => Arc::new(MemoryStorage::new()),
+        StorageConfig::S3(ref s3) => Arc::new(S3Storage::new(s3.clone())?),
+    };
+    Ok(storage)
+}
+
+
+pub fn not_found(key: &str) -> Error {
+    Error::new(ErrorKind::NotFound, format!("no object {}", key))
+}
+
+
+/// Ends the inner stream of chunks after `remaining` bytes.
+pub struct Limit<S> {
+    inner: S,
+    remaining: u64,
+}
+
+impl<S> Limit<S> {
+    pub fn new(inner: S, remaining: u64) -> Self {
+        Limit { inner, remaining }
+    }
+}
+
+impl<S> Stream for Limit<S>
+where
+    S: Stream<Item=Chunk, Error=Error>,
+{
+    type Item = Chunk;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, Error> {
+        if self.remaining == 0 {
+            return Ok(Async::Ready(None));
+        }
+        match self.inner.poll()? {
+            Async::Ready(Some(chunk)) => {
+                let mut bytes = chunk.into_bytes();
+                if bytes.len() as u64 > self.remaining {
+                    bytes.truncate(self.remaining as usize);
+                }
+                self.remaining -= bytes.len() as u64;
+                Ok(Async::Ready(Some(bytes.into())))
+            },
+            Async::Ready(None) => {
+                let msg = format!("object ended {} bytes before the range", self.remaining);
+                Err(Error::new(ErrorKind::UnexpectedEof, msg))
+            },
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
+
+/// Drops the first `remaining` bytes of the inner stream of chunks.
+pub struct Skip<S> {
+    inner: S,
+    remaining: u64,
+}
+
+impl<S> Skip<S> {
+    pub fn new(inner: S, remaining: u64) -> Self {
+        Skip { inner, remaining }
+    }
+}
+
+impl<S> Stream for Skip<S>
+where
+    S: Stream<Item=Chunk, Error=Error>,
+{
+    type Item = Chunk;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, Error> {
+        loop {
+            let chunk = match self.inner.poll()? {
+                Async::Ready(Some(chunk)) => chunk,
+                other => return Ok(other),
+            };
+            if self.remaining == 0 {
+                return Ok(Async::Ready(Some(chunk)));
+            }
+            let mut bytes = chunk.into_bytes();
+            if bytes.len() as u64 > self.remaining {
+                let rest = bytes.split_off(self.remaining as usize);
+                self.remaining = 0;
+                return Ok(Async::Ready(Some(rest.into())));
+            }
+            self.remaining -= bytes.len() as u64;
+        }
+    }
+}
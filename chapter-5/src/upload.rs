@@ -0,0 +1,281 @@
+use chrono::Utc;
+use futures::{future, Future, Stream};
+use hyper_staticfile::FileChunkStream;
+use hyper::{Body, Request, Response, StatusCode};
+use hyper::header::CONTENT_LENGTH;
+use rand::{Rng, thread_rng};
+use rand::distributions::Alphanumeric;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::Error;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tokio::fs::File;
+use crate::config::Config;
+use crate::encoding::{CodedStream, Encoding};
+use crate::meta::{self, Meta, Origin};
+use crate::storage::SharedStorage;
+use crate::{digest, message_response, other, ResponseFuture};
+
+
+/// How many bytes from the start of an upload are used to detect its type.
+const SNIFF_LEN: usize = 8192;
+
+
+/// An upload refused because of its content, answered with `status`.
+#[derive(Debug)]
+pub struct Rejected {
+    status: StatusCode,
+    message: String,
+}
+
+impl Rejected {
+    /// Wraps the rejection in an I/O error, so it can end a body stream.
+    pub fn error(status: StatusCode, message: String) -> Error {
+        Error::other(Rejected { status, message })
+    }
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+
+/// Responds to a rejected upload, other errors are passed on.
+pub fn rejection_response(err: Error) -> Result<Response<Body>, Error> {
+    match err.get_ref().and_then(|inner| inner.downcast_ref::<Rejected>()) {
+        Some(rejected) => Ok(message_response(rejected.status, rejected.message.clone())),
+        None => Err(err),
+    }
+}
+
+
+pub fn too_large(max_size: u64) -> Error {
+    let message = format!("uploads are limited to {} bytes", max_size);
+    Rejected::error(StatusCode::PAYLOAD_TOO_LARGE, message)
+}
+
+
+/// Checks an upload while it's received: its size against the limit, and
+/// the media type sniffed from its first bytes against the allow-list.
+pub struct Inspector {
+    config: Config,
+    size: u64,
+    head: Vec<u8>,
+    content_type: Option<String>,
+}
+
+impl Inspector {
+    pub fn new(config: &Config) -> Self {
+        Inspector {
+            config: config.clone(),
+            size: 0,
+            head: Vec::with_capacity(SNIFF_LEN),
+            content_type: None,
+        }
+    }
+
+    pub fn inspect(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        self.size += chunk.len() as u64;
+        if self.size > self.config.max_size {
+            return Err(too_large(self.config.max_size));
+        }
+        if self.content_type.is_none() {
+            let take = (SNIFF_LEN - self.head.len()).min(chunk.len());
+            self.head.extend_from_slice(&chunk[..take]);
+            if self.head.len() == SNIFF_LEN {
+                self.check_type()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_type(&mut self) -> Result<(), Error> {
+        let content_type = sniff(&self.head);
+        if !self.config.is_allowed(&content_type) {
+            let message = format!("{} content is not allowed", content_type);
+            return Err(Rejected::error(StatusCode::UNSUPPORTED_MEDIA_TYPE, message));
+        }
+        self.content_type = Some(content_type);
+        Ok(())
+    }
+
+    /// Returns the size and the media type of the whole upload.
+    pub fn finish(mut self) -> Result<(u64, String), Error> {
+        if self.content_type.is_none() {
+            self.check_type()?;
+        }
+        Ok((self.size, self.content_type.unwrap_or_default()))
+    }
+}
+
+
+fn sniff(head: &[u8]) -> String {
+    if let Some(kind) = infer::get(head) {
+        return kind.mime_type().to_owned();
+    }
+    // text has no magic bytes, but the head may end in the middle of a character
+    let is_utf8 = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+    if is_utf8 && !head.contains(&0) {
+        "text/plain".to_owned()
+    } else {
+        "application/octet-stream".to_owned()
+    }
+}
+
+
+/// `POST /upload` stores the body as one file and responds with its digest.
+pub fn upload(req: Request<Body>, config: &Config, storage: &SharedStorage, peer: SocketAddr)
+    -> ResponseFuture
+{
+    let length = req.headers().get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if length.is_some_and(|length| length > config.max_size) {
+        return Box::new(future::result(rejection_response(too_large(config.max_size))));
+    }
+    let origin = match Origin::from_request(&req, peer) {
+        Ok(origin) => origin,
+        Err(message) => {
+            return Box::new(future::ok(message_response(StatusCode::BAD_REQUEST, message)));
+        },
+    };
+    let name: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(20)
+        .collect();
+    let partpath = config.files.join(format!(".{}.part", name));
+    let config = config.clone();
+    let storage = storage.clone();
+    let inspector = Inspector::new(&config);
+    let create_file = File::create(partpath.clone());
+    let write = create_file.and_then(|file| {
+        req.into_body()
+            .map_err(other)
+            .fold((file, Sha256::new(), inspector), |(file, mut hasher, mut inspector), chunk| {
+                if let Err(err) = inspector.inspect(&chunk) {
+                    return future::Either::A(future::err(err));
+                }
+                hasher.input(&chunk);
+                let write = tokio::io::write_all(file, chunk)
+                    .map(|(file, _)| (file, hasher, inspector));
+                future::Either::B(write)
+            })
+    });
+    let cleanup = partpath.clone();
+    let body = write
+        .and_then(move |(_, hasher, inspector)| {
+            future::result(inspector.finish()).and_then(move |(size, content_type)| {
+                store(partpath, &config, &storage, hasher, size, content_type, origin)
+            })
+        })
+        .or_else(move |err| {
+            tokio::fs::remove_file(cleanup).then(|_| rejection_response(err))
+        });
+    Box::new(body)
+}
+
+
+/// Compresses a finished upload into a new part file next to it.
+///
+/// Gives the size of the compressed file, the caller removes it.
+fn compress(partpath: PathBuf, packed: PathBuf, encoding: Encoding)
+    -> impl Future<Item=u64, Error=Error>
+{
+    let input = File::open(partpath)
+        .map(FileChunkStream::new)
+        .flatten_stream();
+    File::create(packed).and_then(move |file| {
+        CodedStream::new(input, encoding.encoder())
+            .fold((file, 0), |(file, size), chunk| {
+                let len = chunk.len() as u64;
+                tokio::io::write_all(file, chunk).map(move |(file, _)| (file, size + len))
+            })
+            .map(|(_, size)| size)
+    })
+}
+
+
+/// Puts a finished upload into the storage under its digest and records its metadata.
+///
+/// If a file with the same content is already stored the upload is dropped
+/// and the existing file is kept, in that case the status is `200 OK`
+/// instead of `201 Created`. The body is the digest either way.
+///
+/// Uploads of the types set up for compression are stored compressed,
+/// unless that doesn't make them smaller.
+pub fn store(
+    partpath: PathBuf,
+    config: &Config,
+    storage: &SharedStorage,
+    hasher: Sha256,
+    size: u64,
+    content_type: String,
+    origin: Origin,
+) -> impl Future<Item=Response<Body>, Error=Error> {
+    let digest = digest::to_hex(hasher);
+    let encoding = config.encoding_for(&content_type);
+    let owners = vec![origin.uploader.clone()];
+    let mut meta = Meta {
+        id: digest.clone(),
+        origin,
+        content_type,
+        size,
+        encoding: None,
+        uploaded_at: Utc::now(),
+        owners,
+    };
+    let storage = storage.clone();
+    let key = digest.clone();
+    let packed = partpath.with_extension("packed.part");
+    let cleanup = (partpath.clone(), packed.clone());
+    storage.stat(&key)
+        .and_then({
+            let storage = storage.clone();
+            move |info| match (info, encoding) {
+                (Some(_), _) => future::Either::A(future::ok((StatusCode::OK, None))),
+                (None, None) => {
+                    let put = storage.put_file(&key, partpath, size);
+                    future::Either::B(future::Either::A(put.map(|_| (StatusCode::CREATED, None))))
+                },
+                (None, Some(encoding)) => {
+                    let put = compress(partpath.clone(), packed.clone(), encoding)
+                        .and_then(move |packed_size| {
+                            if packed_size < size {
+                                let put = storage.put_file(&key, packed, packed_size);
+                                future::Either::A(put.map(move |_| Some(encoding)))
+                            } else {
+                                let put = storage.put_file(&key, partpath, size);
+                                future::Either::B(put.map(|_| None))
+                            }
+                        })
+                        .map(|encoding| (StatusCode::CREATED, encoding));
+                    future::Either::B(future::Either::B(put))
+                },
+            }
+        })
+        .then(move |res| {
+            // the storage may have moved the files already
+            let (partpath, packed) = cleanup;
+            tokio::fs::remove_file(partpath)
+                .then(|_| tokio::fs::remove_file(packed))
+                .then(move |_| res)
+        })
+        .and_then(move |(status, encoding)| {
+            meta.encoding = encoding;
+            meta::write(&storage, meta).map(move |_| status)
+        })
+        .map(move |status| {
+            Response::builder()
+                .status(status)
+                .body(digest.into())
+                .unwrap()
+        })
+}
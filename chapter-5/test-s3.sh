@@ -0,0 +1,40 @@
+# Runs the service against a local MinIO in Docker with the s3 storage.
+MINIO=127.0.0.1:9000
+SERVICE=127.0.0.1:8080
+
+export MINIO_ROOT_USER=minioadmin
+export MINIO_ROOT_PASSWORD=minioadmin
+
+cargo build
+
+echo "start minio $MINIO"
+docker run -d --rm --name image-service-minio -p $MINIO:9000 \
+    -e MINIO_ROOT_USER -e MINIO_ROOT_PASSWORD minio/minio server /data
+sleep 3
+docker run --rm --network host --entrypoint sh minio/mc -c \
+    "mc alias set local http://$MINIO $MINIO_ROOT_USER $MINIO_ROOT_PASSWORD && mc mb local/files"
+
+echo "start service $SERVICE"
+STORAGE=s3 S3_ENDPOINT=http://$MINIO S3_BUCKET=files \
+    S3_ACCESS_KEY=$MINIO_ROOT_USER S3_SECRET_KEY=$MINIO_ROOT_PASSWORD \
+    RUST_BACKTRACE=1 target/debug/image-service > service.log 2>&1 &
+SERVICE_PID=$!
+sleep 1
+
+echo "hello from the s3 storage" > upload.txt
+ID=$(curl -s --data-binary @upload.txt -H 'Content-Type: text/plain' http://$SERVICE/upload)
+echo "uploaded $ID"
+
+curl -s http://$SERVICE/download/$ID
+curl -s -H 'Range: bytes=6-9' http://$SERVICE/download/$ID
+echo
+curl -s http://$SERVICE/files/$ID/meta
+echo
+
+kill $SERVICE_PID
+docker stop image-service-minio
+
+echo SERVICE
+cat service.log
+
+rm service.log upload.txt
@@ -1,10 +1,68 @@
-use grpc_ring::Remote;
+use grpc_ring::grpc::{Candidate, RollCall};
+use grpc_ring::{LinkConfig, Remote};
 use std::env;
 
+const USAGE: &str =
+    "usage: grpc-ring-client \
+     [roll-call | last-roll-call | members | leave [ADDRESS] | elect | leader | watch]";
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let next = env::var("NEXT")?;
-    let mut remote = Remote::new(next).await?;
-    remote.start_roll_call().await.unwrap();
+    let config = LinkConfig::from_env()?;
+    let mut remote = Remote::new(next.clone(), &config).await?;
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] | ["roll-call"] => {
+            remote.start_roll_call(RollCall::default()).await?;
+        },
+        ["last-roll-call"] => {
+            let result = remote.get_last_roll_call().await?;
+            let token = result.roll_call.unwrap_or_default();
+            println!("roll call {} started by {}", token.id, token.initiator);
+            for hop in result.hops {
+                println!("{} -> {}: {} ms", hop.from, hop.to, hop.latency);
+            }
+        },
+        ["members"] => {
+            let members = remote.get_members().await?;
+            println!("version {}", members.version);
+            for member in members.members {
+                println!("{}", member.address);
+            }
+        },
+        // without an address the node at NEXT leaves
+        ["leave"] => {
+            remote.leave(next).await?;
+        },
+        ["leave", address] => {
+            remote.leave(address.to_string()).await?;
+        },
+        ["elect"] => {
+            remote.elect(Candidate::default()).await?;
+        },
+        ["leader"] => {
+            let leader = remote.get_leader().await?;
+            println!("{} (id {})", leader.address, leader.id);
+        },
+        ["watch"] => {
+            let mut events = remote.watch_events().await?;
+            while let Some(event) = events.message().await? {
+                let mut line = format!("{} {} {} {} {}",
+                    event.timestamp, event.node, event.kind().as_str_name(), event.action, event.token);
+                if !event.peer.is_empty() {
+                    line += &format!(" -> {}", event.peer);
+                }
+                if !event.error.is_empty() {
+                    line += &format!(": {}", event.error);
+                }
+                println!("{}", line);
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        },
+    }
     Ok(())
 }
@@ -0,0 +1,54 @@
+use crate::grpc::{Hop, Mark, RollCall, RollCallResult};
+
+impl From<Mark> for ring_core::Mark {
+    fn from(mark: Mark) -> Self {
+        Self {
+            node: mark.node,
+            timestamp: mark.timestamp,
+        }
+    }
+}
+
+impl From<ring_core::Mark> for Mark {
+    fn from(mark: ring_core::Mark) -> Self {
+        Self {
+            node: mark.node,
+            timestamp: mark.timestamp,
+        }
+    }
+}
+
+impl From<RollCall> for ring_core::RollCall {
+    fn from(token: RollCall) -> Self {
+        Self {
+            id: token.id,
+            initiator: token.initiator,
+            marks: token.marks.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<ring_core::RollCall> for RollCall {
+    fn from(token: ring_core::RollCall) -> Self {
+        Self {
+            id: token.id,
+            initiator: token.initiator,
+            marks: token.marks.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<ring_core::RollCallResult> for RollCallResult {
+    fn from(result: ring_core::RollCallResult) -> Self {
+        Self {
+            roll_call: Some(result.roll_call.into()),
+            hops: result.hops.into_iter()
+                .map(|hop| Hop {
+                    from: hop.from,
+                    to: hop.to,
+                    latency: hop.latency,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+use crate::grpc::Candidate;
+
+/// What a node does with an election message that reached it.
+#[derive(Debug)]
+pub enum Vote {
+    /// Forward this candidate to the successor.
+    Pass(Candidate),
+    /// The node's own candidate went around the ring, it's the leader.
+    Won(Candidate),
+    /// A higher candidate is already on its way around, drop this one.
+    Drop,
+}
+
+/// A node's part in electing a leader the Chang–Roberts way: every node
+/// passes on the higher of the candidate it got and itself, and the one that
+/// gets its own candidate back has won.
+#[derive(Debug)]
+pub struct Elector {
+    me: Candidate,
+    timeout: Duration,
+    /// When this node took part in the running election.
+    since: Option<Instant>,
+}
+
+impl Elector {
+    /// An election that doesn't finish within `timeout` may be started again.
+    pub fn new(id: u64, address: String, timeout: Duration) -> Self {
+        Self {
+            me: Candidate { id, address },
+            timeout,
+            since: None,
+        }
+    }
+
+    pub fn me(&self) -> &Candidate {
+        &self.me
+    }
+
+    /// Whether this node takes part in an election that hasn't timed out.
+    pub fn is_running(&self) -> bool {
+        self.since.is_some_and(|since| since.elapsed() < self.timeout)
+    }
+
+    /// Stands for leader, returns the candidate to send to the successor.
+    pub fn start(&mut self) -> Candidate {
+        self.since = Some(Instant::now());
+        self.me.clone()
+    }
+
+    pub fn vote(&mut self, candidate: Candidate) -> Vote {
+        let theirs = (candidate.id, &candidate.address);
+        let mine = (self.me.id, &self.me.address);
+        if theirs == mine {
+            self.since = None;
+            Vote::Won(candidate)
+        } else if theirs > mine {
+            self.since = Some(Instant::now());
+            Vote::Pass(candidate)
+        } else if !self.is_running() {
+            Vote::Pass(self.start())
+        } else {
+            Vote::Drop
+        }
+    }
+
+    /// Ends the election, returns `false` if the announcement of this node's
+    /// own win went around the ring and should stop here.
+    pub fn elected(&mut self, leader: &Candidate) -> bool {
+        self.since = None;
+        *leader != self.me
+    }
+}
+
+/// The id of a node that wasn't given one, taken from its address.
+pub fn default_id(address: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    address.hash(&mut hasher);
+    hasher.finish()
+}
@@ -1,20 +1,60 @@
 use std::fmt;
+use tonic::{Code, Status};
 
+/// An error of a call to a node, or of this node before it could make one.
 #[derive(Debug)]
 pub struct RingGrpcError {
+    code: Code,
     message: String,
 }
 
 impl RingGrpcError {
+    /// An error of this node, like a bad config, that retrying won't fix.
     pub fn new(msg: &str) -> Self {
         RingGrpcError {
+            code: Code::Internal,
             message: msg.to_string(),
         }
     }
+
+    /// The gRPC code the node answered with, `Internal` for errors of this node.
+    pub fn code(&self) -> Code {
+        self.code
+    }
+
+    /// Whether the call may succeed if made again.
+    ///
+    /// A call that timed out isn't retried, the node may have handled it.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.code, Code::Unavailable | Code::ResourceExhausted | Code::Aborted)
+    }
+
+    /// Whether the node couldn't be reached or didn't answer in time, rather
+    /// than refused the call. A timed out call ends as `Cancelled`.
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self.code, Code::Unavailable | Code::DeadlineExceeded | Code::Cancelled)
+    }
+}
+
+impl From<Status> for RingGrpcError {
+    fn from(status: Status) -> Self {
+        RingGrpcError {
+            code: status.code(),
+            message: status.message().to_string(),
+        }
+    }
+}
+
+impl From<RingGrpcError> for Status {
+    fn from(err: RingGrpcError) -> Self {
+        Status::new(err.code, err.message)
+    }
 }
 
 impl fmt::Display for RingGrpcError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.message)
+        write!(f, "{}: {}", self.code.description(), self.message)
     }
-} 
+}
+
+impl std::error::Error for RingGrpcError {}
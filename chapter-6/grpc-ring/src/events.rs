@@ -0,0 +1,52 @@
+use tokio::sync::broadcast;
+use crate::grpc::{event::Kind, Event};
+
+/// Events kept for an observer that falls behind, older ones are dropped.
+const CAPACITY: usize = 256;
+
+/// Sends the events of a node to whoever watches it.
+#[derive(Clone, Debug)]
+pub struct Events {
+    node: String,
+    sender: broadcast::Sender<Event>,
+}
+
+impl Events {
+    pub fn new(node: String) -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self {
+            node,
+            sender,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// An event of this node happening now, about an action described by
+    /// its name and token.
+    pub fn event(&self, kind: Kind, (action, token): (&str, &str)) -> Event {
+        Event {
+            kind: kind as i32,
+            node: self.node.clone(),
+            timestamp: ring_core::roll_call::now(),
+            action: action.to_owned(),
+            token: token.to_owned(),
+            ..Event::default()
+        }
+    }
+
+    pub fn send(&self, event: Event) {
+        // nobody may be watching
+        let _ = self.sender.send(event);
+    }
+}
+
+/// The name and the token of a roll call action, as events show them.
+pub fn describe(action: &ring_core::Action) -> (&'static str, &str) {
+    match action {
+        ring_core::Action::StartRollCall(token) => ("START_ROLL_CALL", &token.id),
+        ring_core::Action::MarkItself(token) => ("MARK_ITSELF", &token.id),
+    }
+}
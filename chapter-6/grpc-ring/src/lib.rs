@@ -1,57 +1,189 @@
 pub mod grpc {
     tonic::include_proto!("ringproto");
 }
+mod convert;
+pub mod election;
+pub mod events;
 mod error;
+pub mod membership;
+pub mod retry;
+pub mod tls;
 
+use std::future::Future;
 use std::time::Duration;
-use tonic::{Request, Response, Status, transport::{Channel, Endpoint}};
-use crate::error::RingGrpcError;
+use async_trait::async_trait;
+use ring_core::json_rpc::JsonRpcTransport;
+use ring_core::{Action, Peer, RingTransport, TransportError};
+use tonic::{Request, Response, Status, Streaming, transport::{Channel, Endpoint}};
+pub use crate::error::RingGrpcError;
+use crate::retry::RetryPolicy;
+use crate::tls::TlsConfig;
 use crate::grpc::ring_client::RingClient;
-use crate::grpc::Empty;
+use crate::grpc::{Candidate, Empty, Event, Member, Members, RollCall, RollCallResult};
+
+/// How a node or a client links to the nodes of a ring.
+#[derive(Clone, Debug, Default)]
+pub struct LinkConfig {
+    pub tls: Option<TlsConfig>,
+    pub retry: RetryPolicy,
+}
+
+impl LinkConfig {
+    pub fn from_env() -> Result<Self, RingGrpcError> {
+        Ok(Self {
+            tls: TlsConfig::from_env()?,
+            retry: RetryPolicy::from_env()?,
+        })
+    }
+}
 
 pub struct Remote<Channel>  {
     client: RingClient<Channel>,
+    retry: RetryPolicy,
 }
 
 impl Remote<Channel> { 
-    pub async fn new(addr: String) -> Result<Self, Box<dyn std::error::Error>> {
-        let channel = Endpoint::new(addr)?
+    /// With a TLS config only `https` addresses are linked to.
+    pub async fn new(addr: String, config: &LinkConfig) -> Result<Self, RingGrpcError> {
+        let mut endpoint = Endpoint::new(addr.clone())
+            .map_err(|err| RingGrpcError::new(&format!("bad address {}: {}", addr, err)))?
             .timeout(Duration::from_secs(3))
-            .connect_timeout(Duration::from_secs(10))
-            .connect_lazy();
+            .connect_timeout(Duration::from_secs(10));
+        if let Some(tls) = &config.tls {
+            if !addr.starts_with("https://") {
+                let msg = format!("plaintext link to {} refused", addr);
+                return Err(RingGrpcError::new(&msg));
+            }
+            endpoint = endpoint.tls_config(tls.client())
+                .map_err(|err| RingGrpcError::new(&err.to_string()))?;
+        }
+        let channel = endpoint.connect_lazy();
         let client = RingClient::new(channel);
         Ok(Self {
             client,
+            retry: config.retry,
         })
     }
 
-    pub async fn start_roll_call(&mut self) -> Result<Empty, RingGrpcError> {
-        let response = self.client
-            .start_roll_call(Request::new(Empty {}))
-            .await;
-        self.get_from_response(response)
+    pub async fn start_roll_call(&mut self, token: RollCall) -> Result<Empty, RingGrpcError> {
+        self.call(|mut client| {
+            let token = token.clone();
+            async move { client.start_roll_call(Request::new(token)).await }
+        }).await
+    }
+
+    pub async fn mark_itself(&mut self, token: RollCall) -> Result<Empty, RingGrpcError> {
+        self.call(|mut client| {
+            let token = token.clone();
+            async move { client.mark_itself(Request::new(token)).await }
+        }).await
+    }
+
+    pub async fn get_last_roll_call(&mut self) -> Result<RollCallResult, RingGrpcError> {
+        self.call(|mut client| async move {
+            client.get_last_roll_call(Request::new(Empty {})).await
+        }).await
+    }
+
+    pub async fn join(&mut self, address: String) -> Result<Members, RingGrpcError> {
+        self.call(|mut client| {
+            let address = address.clone();
+            async move { client.join(Request::new(Member { address })).await }
+        }).await
+    }
+
+    pub async fn leave(&mut self, address: String) -> Result<Members, RingGrpcError> {
+        self.call(|mut client| {
+            let address = address.clone();
+            async move { client.leave(Request::new(Member { address })).await }
+        }).await
+    }
+
+    pub async fn get_members(&mut self) -> Result<Members, RingGrpcError> {
+        self.call(|mut client| async move {
+            client.get_members(Request::new(Empty {})).await
+        }).await
+    }
+
+    pub async fn elect(&mut self, candidate: Candidate) -> Result<Empty, RingGrpcError> {
+        self.call(|mut client| {
+            let candidate = candidate.clone();
+            async move { client.elect(Request::new(candidate)).await }
+        }).await
+    }
+
+    pub async fn elected(&mut self, leader: Candidate) -> Result<Empty, RingGrpcError> {
+        self.call(|mut client| {
+            let leader = leader.clone();
+            async move { client.elected(Request::new(leader)).await }
+        }).await
     }
 
-    pub async fn mark_itself(&mut self) -> Result<Empty, RingGrpcError> {
-        let response = self.client
-            .mark_itself(Request::new(Empty {}))
-            .await;
-        self.get_from_response(response)
+    pub async fn get_leader(&mut self) -> Result<Candidate, RingGrpcError> {
+        self.call(|mut client| async move {
+            client.get_leader(Request::new(Empty {})).await
+        }).await
     }
 
-    fn get_from_response(&self, response: Result<Response<Empty>, Status>)
-        -> Result<Empty, RingGrpcError>
+    pub async fn watch_events(&mut self) -> Result<Streaming<Event>, RingGrpcError> {
+        self.call(|mut client| async move {
+            client.watch_events(Request::new(Empty {})).await
+        }).await
+    }
+
+    /// Makes a call, again after a pause for as long as it fails in a way
+    /// that may pass.
+    async fn call<T, F, R>(&self, call: F) -> Result<T, RingGrpcError>
+    where
+        F: Fn(RingClient<Channel>) -> R,
+        R: Future<Output = Result<Response<T>, Status>>,
     {
-        match response {
-            Ok(resp) => Ok(resp.into_inner()),
-            Err(status) => {
-                log::error!("Error response with status {}", status);
-                Err(
-                    RingGrpcError::new(
-                        format!("Internal error: {}", status).as_str()
-                        )
-                    )
-            },
+        let mut retry = 0;
+        loop {
+            let err = match call(self.client.clone()).await {
+                Ok(resp) => return Ok(resp.into_inner()),
+                Err(status) => RingGrpcError::from(status),
+            };
+            retry += 1;
+            if !err.is_retryable() || retry >= self.retry.attempts {
+                log::error!("Error response with status {}", err);
+                return Err(err);
+            }
+            let delay = self.retry.delay(retry);
+            log::warn!("Retrying in {:?} after {}", delay, err);
+            tokio::time::sleep(delay).await;
         }
     }
 }
+
+#[async_trait]
+impl RingTransport for Remote<Channel> {
+    async fn send(&mut self, action: &Action) -> Result<(), TransportError> {
+        let result = match action {
+            Action::StartRollCall(token) => self.start_roll_call(token.clone().into()).await,
+            Action::MarkItself(token) => self.mark_itself(token.clone().into()).await,
+        };
+        result
+            .map(drop)
+            .map_err(|err| TransportError::new(&err.to_string()))
+    }
+}
+
+/// Links to a node of a ring, whichever protocol it speaks.
+///
+/// JSON-RPC is plaintext only, so there are no such links with a TLS config.
+pub async fn connect(address: &str, config: &LinkConfig)
+    -> Result<Box<dyn RingTransport>, TransportError>
+{
+    match Peer::parse(address) {
+        Peer::Grpc(addr) => {
+            let remote = Remote::new(addr, config).await
+                .map_err(|err| TransportError::new(&err.to_string()))?;
+            Ok(Box::new(remote))
+        },
+        Peer::JsonRpc(addr) if config.tls.is_some() => {
+            Err(TransportError::new(&format!("plaintext link to {} refused", addr)))
+        },
+        Peer::JsonRpc(addr) => Ok(Box::new(JsonRpcTransport::new(&addr)?)),
+    }
+}
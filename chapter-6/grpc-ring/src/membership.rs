@@ -0,0 +1,119 @@
+use crate::grpc::{Member, Members};
+
+/// A node's view of the ring: the addresses of all nodes in ring order.
+///
+/// Changes are made on any node and bump the version, other nodes pick them
+/// up when they check on their successor and it has a newer list.
+#[derive(Debug)]
+pub struct Membership {
+    me: String,
+    version: u64,
+    members: Vec<String>,
+    left: bool,
+}
+
+impl Membership {
+    /// A ring with this node alone.
+    pub fn new(me: String) -> Self {
+        Self {
+            members: vec![me.clone()],
+            me,
+            version: 0,
+            left: false,
+        }
+    }
+
+    /// A node that is about to join a ring and isn't a member yet.
+    pub fn joining(me: String) -> Self {
+        Self {
+            members: Vec::new(),
+            ..Self::new(me)
+        }
+    }
+
+    pub fn me(&self) -> &str {
+        &self.me
+    }
+
+    pub fn is_member(&self) -> bool {
+        self.contains(&self.me)
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        self.members.iter().any(|member| member == address)
+    }
+
+    /// Whether this node left the ring on purpose and shouldn't join again.
+    pub fn has_left(&self) -> bool {
+        self.left
+    }
+
+    /// The node to forward to, this node itself if it's alone.
+    pub fn successor(&self) -> Option<&str> {
+        let pos = self.members.iter().position(|member| *member == self.me)?;
+        let next = (pos + 1) % self.members.len();
+        Some(&self.members[next])
+    }
+
+    /// Adds a member right before this node, moving it if it's already there.
+    pub fn join(&mut self, member: String) {
+        self.members.retain(|m| *m != member);
+        let pos = self.members.iter()
+            .position(|m| *m == self.me)
+            .unwrap_or(self.members.len());
+        self.members.insert(pos, member);
+        self.version += 1;
+    }
+
+    /// Removes a member, returns `false` if it wasn't in the ring.
+    pub fn remove(&mut self, member: &str) -> bool {
+        let len = self.members.len();
+        self.members.retain(|m| m != member);
+        if self.members.len() == len {
+            return false;
+        }
+        self.version += 1;
+        true
+    }
+
+    /// Marks this node as gone from the ring, `members` is the list without it.
+    pub fn leave(&mut self, members: Members) {
+        self.replace(members);
+        self.left = true;
+    }
+
+    /// Takes over the list of another node if it's newer, returns whether it was.
+    ///
+    /// Lists of the same version made on different nodes are ordered by their
+    /// members, so all nodes settle on the same one.
+    pub fn adopt(&mut self, members: Members) -> bool {
+        let addresses = addresses(&members);
+        if (members.version, &addresses) <= (self.version, &self.members) {
+            return false;
+        }
+        self.version = members.version;
+        self.members = addresses;
+        true
+    }
+
+    /// Takes over the list of another node whatever its version.
+    pub fn replace(&mut self, members: Members) {
+        self.version = members.version;
+        self.members = addresses(&members);
+    }
+
+    pub fn to_proto(&self) -> Members {
+        Members {
+            version: self.version,
+            members: self.members.iter()
+                .map(|address| Member { address: address.clone() })
+                .collect(),
+        }
+    }
+}
+
+fn addresses(members: &Members) -> Vec<String> {
+    members.members.iter()
+        .map(|member| member.address.clone())
+        .collect()
+}
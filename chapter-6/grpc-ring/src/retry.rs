@@ -0,0 +1,54 @@
+use std::env;
+use std::time::Duration;
+use rand::Rng;
+use crate::error::RingGrpcError;
+
+/// How calls to other nodes are retried: with a pause that doubles every
+/// time up to `max`, half of it random so nodes don't retry all at once.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Calls made in all, the first one included.
+    pub attempts: u32,
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            base: Duration::from_millis(100),
+            max: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Takes `RETRY_ATTEMPTS`, `RETRY_BASE_MS` and `RETRY_MAX_MS` from the
+    /// environment, the default for those not set.
+    pub fn from_env() -> Result<Self, RingGrpcError> {
+        let default = Self::default();
+        Ok(Self {
+            attempts: var("RETRY_ATTEMPTS")?.unwrap_or(default.attempts as u64).max(1) as u32,
+            base: var("RETRY_BASE_MS")?.map(Duration::from_millis).unwrap_or(default.base),
+            max: var("RETRY_MAX_MS")?.map(Duration::from_millis).unwrap_or(default.max),
+        })
+    }
+
+    /// The pause before retry number `retry`, counted from 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let backoff = self.base.saturating_mul(factor).min(self.max);
+        let half = backoff / 2;
+        half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+    }
+}
+
+fn var(name: &str) -> Result<Option<u64>, RingGrpcError> {
+    match env::var(name) {
+        Ok(value) => value.parse()
+            .map(Some)
+            .map_err(|_| RingGrpcError::new(&format!("{} must be a number", name))),
+        Err(_) => Ok(None),
+    }
+}
@@ -1,5 +1,144 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct Empty {
+pub struct Empty {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Member {
+    #[prost(string, tag = "1")]
+    pub address: ::prost::alloc::string::String,
+}
+/// Addresses of the nodes in ring order, each node forwards to the next one
+/// and the last one to the first. Lists with a higher version replace older
+/// ones.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Members {
+    #[prost(uint64, tag = "1")]
+    pub version: u64,
+    #[prost(message, repeated, tag = "2")]
+    pub members: ::prost::alloc::vec::Vec<Member>,
+}
+/// A node the roll call token passed, with the time it got there in
+/// milliseconds since the Unix epoch.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Mark {
+    #[prost(string, tag = "1")]
+    pub node: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+}
+/// The token of a roll call, sent around the ring and marked by every node.
+/// Clients start a roll call with an empty token.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RollCall {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub initiator: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub marks: ::prost::alloc::vec::Vec<Mark>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Hop {
+    #[prost(string, tag = "1")]
+    pub from: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub to: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "3")]
+    pub latency: u64,
+}
+/// A roll call that went around the ring, with the latency of every hop in
+/// milliseconds, the last one back to the initiator.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RollCallResult {
+    #[prost(message, optional, tag = "1")]
+    pub roll_call: ::core::option::Option<RollCall>,
+    #[prost(message, repeated, tag = "2")]
+    pub hops: ::prost::alloc::vec::Vec<Hop>,
+}
+/// A node standing for leader, nodes with a higher id win and the address
+/// breaks ties. Clients start an election with an empty candidate.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Candidate {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(string, tag = "2")]
+    pub address: ::prost::alloc::string::String,
+}
+/// Something a node did, streamed to the observers of the node.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Event {
+    #[prost(enumeration = "event::Kind", tag = "1")]
+    pub kind: i32,
+    /// The node that did it.
+    #[prost(string, tag = "2")]
+    pub node: ::prost::alloc::string::String,
+    /// Milliseconds since the Unix epoch.
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    /// START_ROLL_CALL, MARK_ITSELF, ELECT, ELECTED or PING, the check of the
+    /// successor.
+    #[prost(string, tag = "4")]
+    pub action: ::prost::alloc::string::String,
+    /// The roll call id, or the address of the candidate of an election.
+    #[prost(string, tag = "5")]
+    pub token: ::prost::alloc::string::String,
+    /// The node the action went to or failed at.
+    #[prost(string, tag = "6")]
+    pub peer: ::prost::alloc::string::String,
+    #[prost(string, tag = "7")]
+    pub error: ::prost::alloc::string::String,
+}
+/// Nested message and enum types in `Event`.
+pub mod event {
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash,
+        PartialOrd,
+        Ord,
+        ::prost::Enumeration
+    )]
+    #[repr(i32)]
+    pub enum Kind {
+        Received = 0,
+        Forwarded = 1,
+        Skipped = 2,
+        PeerFailed = 3,
+    }
+    impl Kind {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Kind::Received => "RECEIVED",
+                Kind::Forwarded => "FORWARDED",
+                Kind::Skipped => "SKIPPED",
+                Kind::PeerFailed => "PEER_FAILED",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "RECEIVED" => Some(Self::Received),
+                "FORWARDED" => Some(Self::Forwarded),
+                "SKIPPED" => Some(Self::Skipped),
+                "PEER_FAILED" => Some(Self::PeerFailed),
+                _ => None,
+            }
+        }
+    }
 }
 /// Generated client implementations.
 pub mod ring_client {
@@ -72,7 +211,7 @@ pub mod ring_client {
         }
         pub async fn start_roll_call(
             &mut self,
-            request: impl tonic::IntoRequest<super::Empty>,
+            request: impl tonic::IntoRequest<super::RollCall>,
         ) -> Result<tonic::Response<super::Empty>, tonic::Status> {
             self.inner
                 .ready()
@@ -91,7 +230,7 @@ pub mod ring_client {
         }
         pub async fn mark_itself(
             &mut self,
-            request: impl tonic::IntoRequest<super::Empty>,
+            request: impl tonic::IntoRequest<super::RollCall>,
         ) -> Result<tonic::Response<super::Empty>, tonic::Status> {
             self.inner
                 .ready()
@@ -108,23 +247,218 @@ pub mod ring_client {
             );
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn get_last_roll_call(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<tonic::Response<super::RollCallResult>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ringproto.Ring/GetLastRollCall",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Adds the member right before the called node.
+        pub async fn join(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Member>,
+        ) -> Result<tonic::Response<super::Members>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/ringproto.Ring/Join");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Removes the member, a node asked to remove itself leaves the ring.
+        pub async fn leave(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Member>,
+        ) -> Result<tonic::Response<super::Members>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/ringproto.Ring/Leave");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Also used by nodes to check on their successor.
+        pub async fn get_members(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<tonic::Response<super::Members>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ringproto.Ring/GetMembers",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Election message, passes the highest candidate seen on.
+        pub async fn elect(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Candidate>,
+        ) -> Result<tonic::Response<super::Empty>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/ringproto.Ring/Elect");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Announces the winner of an election around the ring.
+        pub async fn elected(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Candidate>,
+        ) -> Result<tonic::Response<super::Empty>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/ringproto.Ring/Elected");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn get_leader(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<tonic::Response<super::Candidate>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/ringproto.Ring/GetLeader");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Streams the events of the node as they happen.
+        pub async fn watch_events(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Empty>,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::Event>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ringproto.Ring/WatchEvents",
+            );
+            self.inner.server_streaming(request.into_request(), path, codec).await
+        }
     }
 }
 /// Generated server implementations.
 pub mod ring_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
-    ///Generated trait containing gRPC methods that should be implemented for use with RingServer.
+    /// Generated trait containing gRPC methods that should be implemented for use with RingServer.
     #[async_trait]
     pub trait Ring: Send + Sync + 'static {
         async fn start_roll_call(
             &self,
-            request: tonic::Request<super::Empty>,
+            request: tonic::Request<super::RollCall>,
         ) -> Result<tonic::Response<super::Empty>, tonic::Status>;
         async fn mark_itself(
+            &self,
+            request: tonic::Request<super::RollCall>,
+        ) -> Result<tonic::Response<super::Empty>, tonic::Status>;
+        async fn get_last_roll_call(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<super::RollCallResult>, tonic::Status>;
+        /// Adds the member right before the called node.
+        async fn join(
+            &self,
+            request: tonic::Request<super::Member>,
+        ) -> Result<tonic::Response<super::Members>, tonic::Status>;
+        /// Removes the member, a node asked to remove itself leaves the ring.
+        async fn leave(
+            &self,
+            request: tonic::Request<super::Member>,
+        ) -> Result<tonic::Response<super::Members>, tonic::Status>;
+        /// Also used by nodes to check on their successor.
+        async fn get_members(
             &self,
             request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<super::Members>, tonic::Status>;
+        /// Election message, passes the highest candidate seen on.
+        async fn elect(
+            &self,
+            request: tonic::Request<super::Candidate>,
         ) -> Result<tonic::Response<super::Empty>, tonic::Status>;
+        /// Announces the winner of an election around the ring.
+        async fn elected(
+            &self,
+            request: tonic::Request<super::Candidate>,
+        ) -> Result<tonic::Response<super::Empty>, tonic::Status>;
+        async fn get_leader(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<super::Candidate>, tonic::Status>;
+        /// Server streaming response type for the WatchEvents method.
+        type WatchEventsStream: futures_core::Stream<
+                Item = Result<super::Event, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// Streams the events of the node as they happen.
+        async fn watch_events(
+            &self,
+            request: tonic::Request<super::Empty>,
+        ) -> Result<tonic::Response<Self::WatchEventsStream>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct RingServer<T: Ring> {
@@ -188,7 +522,7 @@ pub mod ring_server {
                 "/ringproto.Ring/StartRollCall" => {
                     #[allow(non_camel_case_types)]
                     struct StartRollCallSvc<T: Ring>(pub Arc<T>);
-                    impl<T: Ring> tonic::server::UnaryService<super::Empty>
+                    impl<T: Ring> tonic::server::UnaryService<super::RollCall>
                     for StartRollCallSvc<T> {
                         type Response = super::Empty;
                         type Future = BoxFuture<
@@ -197,7 +531,7 @@ pub mod ring_server {
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::Empty>,
+                            request: tonic::Request<super::RollCall>,
                         ) -> Self::Future {
                             let inner = self.0.clone();
                             let fut = async move {
@@ -226,7 +560,7 @@ pub mod ring_server {
                 "/ringproto.Ring/MarkItself" => {
                     #[allow(non_camel_case_types)]
                     struct MarkItselfSvc<T: Ring>(pub Arc<T>);
-                    impl<T: Ring> tonic::server::UnaryService<super::Empty>
+                    impl<T: Ring> tonic::server::UnaryService<super::RollCall>
                     for MarkItselfSvc<T> {
                         type Response = super::Empty;
                         type Future = BoxFuture<
@@ -235,7 +569,7 @@ pub mod ring_server {
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::Empty>,
+                            request: tonic::Request<super::RollCall>,
                         ) -> Self::Future {
                             let inner = self.0.clone();
                             let fut = async move { (*inner).mark_itself(request).await };
@@ -259,6 +593,299 @@ pub mod ring_server {
                     };
                     Box::pin(fut)
                 }
+                "/ringproto.Ring/GetLastRollCall" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetLastRollCallSvc<T: Ring>(pub Arc<T>);
+                    impl<T: Ring> tonic::server::UnaryService<super::Empty>
+                    for GetLastRollCallSvc<T> {
+                        type Response = super::RollCallResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).get_last_roll_call(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetLastRollCallSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ringproto.Ring/Join" => {
+                    #[allow(non_camel_case_types)]
+                    struct JoinSvc<T: Ring>(pub Arc<T>);
+                    impl<T: Ring> tonic::server::UnaryService<super::Member>
+                    for JoinSvc<T> {
+                        type Response = super::Members;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Member>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).join(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = JoinSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ringproto.Ring/Leave" => {
+                    #[allow(non_camel_case_types)]
+                    struct LeaveSvc<T: Ring>(pub Arc<T>);
+                    impl<T: Ring> tonic::server::UnaryService<super::Member>
+                    for LeaveSvc<T> {
+                        type Response = super::Members;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Member>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).leave(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = LeaveSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ringproto.Ring/GetMembers" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetMembersSvc<T: Ring>(pub Arc<T>);
+                    impl<T: Ring> tonic::server::UnaryService<super::Empty>
+                    for GetMembersSvc<T> {
+                        type Response = super::Members;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_members(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetMembersSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ringproto.Ring/Elect" => {
+                    #[allow(non_camel_case_types)]
+                    struct ElectSvc<T: Ring>(pub Arc<T>);
+                    impl<T: Ring> tonic::server::UnaryService<super::Candidate>
+                    for ElectSvc<T> {
+                        type Response = super::Empty;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Candidate>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).elect(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ElectSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ringproto.Ring/Elected" => {
+                    #[allow(non_camel_case_types)]
+                    struct ElectedSvc<T: Ring>(pub Arc<T>);
+                    impl<T: Ring> tonic::server::UnaryService<super::Candidate>
+                    for ElectedSvc<T> {
+                        type Response = super::Empty;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Candidate>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).elected(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ElectedSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ringproto.Ring/GetLeader" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetLeaderSvc<T: Ring>(pub Arc<T>);
+                    impl<T: Ring> tonic::server::UnaryService<super::Empty>
+                    for GetLeaderSvc<T> {
+                        type Response = super::Candidate;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_leader(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetLeaderSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ringproto.Ring/WatchEvents" => {
+                    #[allow(non_camel_case_types)]
+                    struct WatchEventsSvc<T: Ring>(pub Arc<T>);
+                    impl<T: Ring> tonic::server::ServerStreamingService<super::Empty>
+                    for WatchEventsSvc<T> {
+                        type Response = super::Event;
+                        type ResponseStream = T::WatchEventsStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Empty>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).watch_events(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = WatchEventsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
@@ -1,88 +1,522 @@
-use grpc_ring::grpc::Empty;
+use async_trait::async_trait;
+use grpc_ring::election::{self, Elector, Vote};
+use grpc_ring::events::{self, Events};
+use grpc_ring::grpc::event::Kind;
+use grpc_ring::grpc::{Candidate, Empty, Event, Member, Members, RollCall, RollCallResult};
 use grpc_ring::grpc::ring_server::{Ring, RingServer};
-use grpc_ring::Remote;
-use log::{debug, trace, error, info};
+use grpc_ring::membership::Membership;
+use grpc_ring::{LinkConfig, Remote, RingGrpcError};
+use log::{debug, trace, error, info, warn};
+use ring_core::{Peer, RingTransport, TransportError};
 use std::env;
-use tonic::{transport::Server, Request, Response, Status};
-use tokio::{self, sync::mpsc::{self, Sender, Receiver}};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tonic::{transport::{Channel, Server}, Request, Response, Status};
+use tokio::{self, sync::mpsc::{self, error::TrySendError, Sender, Receiver}};
+use tokio::time::MissedTickBehavior;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tokio_stream::{Stream, StreamExt};
 
 
+/// How often a node checks on its successor.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Failed checks, or forwards, in a row after which the successor is dropped
+/// from the ring.
+const MAX_PING_FAILURES: u32 = 3;
+
+/// How long a node waits for an election to finish before it starts another.
+const ELECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+
+type SharedMembership = Arc<Mutex<Membership>>;
+
+type LastRollCall = Arc<Mutex<Option<RollCallResult>>>;
+
+type SharedLeader = Arc<Mutex<Option<Candidate>>>;
+
 #[derive(Debug)]
 enum Action {
-    StartRollCall,
-    MarkItself,
+    RollCall(ring_core::Action),
+    Elect(Candidate),
+    Elected(Candidate),
+    /// Sent by the health check when the ring has no leader.
+    StartElection,
+}
+
+impl Action {
+    /// The name and the token of the action, as events show them.
+    fn describe(&self) -> (&'static str, &str) {
+        match self {
+            Action::RollCall(action) => events::describe(action),
+            Action::Elect(candidate) => ("ELECT", &candidate.address),
+            Action::Elected(leader) => ("ELECTED", &leader.address),
+            Action::StartElection => ("START_ELECTION", ""),
+        }
+    }
 }
 
 #[derive(Debug)]
 struct RingService {
     sender: Sender<Action>,
+    membership: SharedMembership,
+    last_roll_call: LastRollCall,
+    leader: SharedLeader,
+    config: LinkConfig,
+    events: Events,
 }
 
 impl RingService {
-    fn new(sender: Sender<Action>) -> Self {
+    fn new(
+        sender: Sender<Action>,
+        membership: SharedMembership,
+        last_roll_call: LastRollCall,
+        leader: SharedLeader,
+        config: LinkConfig,
+        events: Events,
+    ) -> Self {
         Self {
             sender,
+            membership,
+            last_roll_call,
+            leader,
+            config,
+            events,
         }
     }
 
     async fn send_action(&self, action: Action) -> Result<Response<Empty>, Status> {
-        self.sender.send(action).await.unwrap();
+        self.sender.send(action).await
+            .map_err(|_| Status::unavailable("the node is shutting down"))?;
         Ok(Response::new(Empty {}))
     }
+
+    fn members(&self) -> Response<Members> {
+        Response::new(self.membership.lock().unwrap().to_proto())
+    }
 }
 
 #[tonic::async_trait]
 impl Ring for RingService {
-    async fn start_roll_call(&self, _: Request<Empty>)
+    type WatchEventsStream = Pin<Box<dyn Stream<Item = Result<Event, Status>> + Send>>;
+
+    async fn start_roll_call(&self, request: Request<RollCall>)
         ->  Result<Response<Empty>, Status>
     {
         trace!("START_ROLL_CALL");
-        self.send_action(Action::StartRollCall).await
+        let token = request.into_inner().into();
+        self.send_action(Action::RollCall(ring_core::Action::StartRollCall(token))).await
     }
 
-    async fn mark_itself(&self, _: Request<Empty>) 
+    async fn mark_itself(&self, request: Request<RollCall>)
         -> Result<Response<Empty>, Status>
     {
         trace!("MARK_INSELF");
-        self.send_action(Action::MarkItself).await
+        let token = request.into_inner().into();
+        self.send_action(Action::RollCall(ring_core::Action::MarkItself(token))).await
+    }
+
+    async fn get_last_roll_call(&self, _: Request<Empty>)
+        -> Result<Response<RollCallResult>, Status>
+    {
+        trace!("GET_LAST_ROLL_CALL");
+        match *self.last_roll_call.lock().unwrap() {
+            Some(ref result) => Ok(Response::new(result.clone())),
+            None => Err(Status::not_found("no roll call has come back yet")),
+        }
+    }
+
+    async fn join(&self, request: Request<Member>)
+        -> Result<Response<Members>, Status>
+    {
+        let member = request.into_inner().address;
+        trace!("JOIN {}", member);
+        let mut membership = self.membership.lock().unwrap();
+        if membership.has_left() {
+            return Err(Status::failed_precondition("node has left the ring"));
+        }
+        if !membership.is_member() {
+            // a node that is still joining itself starts a new ring
+            let me = membership.me().to_owned();
+            *membership = Membership::new(me);
+        }
+        membership.join(member);
+        drop(membership);
+        Ok(self.members())
+    }
+
+    async fn leave(&self, request: Request<Member>)
+        -> Result<Response<Members>, Status>
+    {
+        let member = request.into_inner().address;
+        trace!("LEAVE {}", member);
+        let successor = {
+            let mut membership = self.membership.lock().unwrap();
+            if member != membership.me() {
+                membership.remove(&member);
+                return Ok(Response::new(membership.to_proto()));
+            }
+            membership.successor()
+                .filter(|successor| *successor != member)
+                .map(str::to_owned)
+        };
+        // the successor stays in the ring and spreads the change
+        let members = match successor {
+            Some(successor) => {
+                let mut remote = Remote::new(successor, &self.config).await?;
+                remote.leave(member).await?
+            },
+            None => Members::default(),
+        };
+        info!("Left the ring");
+        self.membership.lock().unwrap().leave(members);
+        Ok(self.members())
+    }
+
+    async fn get_members(&self, _: Request<Empty>)
+        -> Result<Response<Members>, Status>
+    {
+        trace!("GET_MEMBERS");
+        Ok(self.members())
+    }
+
+    async fn elect(&self, request: Request<Candidate>)
+        -> Result<Response<Empty>, Status>
+    {
+        trace!("ELECT");
+        self.send_action(Action::Elect(request.into_inner())).await
+    }
+
+    async fn elected(&self, request: Request<Candidate>)
+        -> Result<Response<Empty>, Status>
+    {
+        trace!("ELECTED");
+        self.send_action(Action::Elected(request.into_inner())).await
+    }
+
+    async fn get_leader(&self, _: Request<Empty>)
+        -> Result<Response<Candidate>, Status>
+    {
+        trace!("GET_LEADER");
+        match *self.leader.lock().unwrap() {
+            Some(ref leader) => Ok(Response::new(leader.clone())),
+            None => Err(Status::not_found("no leader has been elected yet")),
+        }
+    }
+
+    async fn watch_events(&self, _: Request<Empty>)
+        -> Result<Response<Self::WatchEventsStream>, Status>
+    {
+        trace!("WATCH_EVENTS");
+        let stream = BroadcastStream::new(self.events.subscribe())
+            .filter_map(|event| match event {
+                Ok(event) => Some(Ok(event)),
+                Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                    warn!("An observer missed {} events", missed);
+                    None
+                },
+            });
+        Ok(Response::new(Box::pin(stream)))
     }
 }
 
-async fn worker_loop(mut receiver: Receiver<Action>)
-    -> Result<(), Box<dyn std::error::Error>>
-{
-    let next = match env::var("NEXT") {
-        Ok(val) => val,
-        Err(e) => {
-            error!("$NEXT is not set: {}", e.to_string());
-            return Err(Box::new(e))
+/// The link of a node to the next one in the ring.
+struct Node {
+    membership: SharedMembership,
+    /// Node to join the ring through, the last known successor.
+    contact: Option<String>,
+    config: LinkConfig,
+    events: Events,
+    remote: Option<(String, Remote<Channel>)>,
+    failures: u32,
+}
+
+impl Node {
+    fn new(
+        membership: SharedMembership,
+        contact: Option<String>,
+        config: LinkConfig,
+        events: Events,
+    ) -> Self {
+        Self {
+            membership,
+            contact,
+            config,
+            events,
+            remote: None,
+            failures: 0,
         }
-    };
-    let mut remote = Remote::new(next).await?;
-    let mut in_roll_call = false;
+    }
+
+    /// The successor, or the contact while this node isn't in the ring.
+    fn target(&mut self) -> Option<String> {
+        let successor = self.membership.lock().unwrap().successor().map(str::to_owned);
+        if successor.is_some() {
+            self.contact = successor;
+        }
+        self.contact.clone()
+    }
+
+    /// Connects to the target, and again whenever it changes.
+    async fn remote(&mut self) -> Option<(String, &mut Remote<Channel>)> {
+        let target = self.target()?;
+        if self.remote.as_ref().map(|(address, _)| address) != Some(&target) {
+            match Remote::new(target.clone(), &self.config).await {
+                Ok(remote) => {
+                    info!("Linked to {}", target);
+                    self.remote = Some((target.clone(), remote));
+                    self.failures = 0;
+                },
+                Err(e) => {
+                    error!("Can't link to {}: {}", target, e);
+                    return None;
+                },
+            }
+        }
+        self.remote.as_mut().map(|(_, remote)| (target, remote))
+    }
+
+    /// Drops a successor that doesn't respond, which links this node to the
+    /// one after it.
+    fn drop_successor(&mut self, address: &str) {
+        let mut membership = self.membership.lock().unwrap();
+        if membership.is_member() && address != membership.me() && membership.remove(address) {
+            warn!("{} doesn't respond, removed it from the ring", address);
+        }
+        self.failures = 0;
+    }
+
+    /// Counts a failed call to the successor, and drops it once it hasn't
+    /// been reached too many times in a row. Returns whether it was dropped.
+    fn failed(&mut self, address: &str, err: &RingGrpcError) -> bool {
+        if !err.is_unreachable() {
+            return false;
+        }
+        self.failures += 1;
+        if self.failures < MAX_PING_FAILURES {
+            return false;
+        }
+        self.drop_successor(address);
+        true
+    }
+
+    /// Passes an action on to the successor, around the ones that keep failing.
+    async fn forward(&mut self, action: &Action) -> Result<(), RingGrpcError> {
+        loop {
+            let (address, remote) = self.remote().await
+                .ok_or_else(|| RingGrpcError::new("no node to link to"))?;
+            let result = match action {
+                Action::RollCall(ring_core::Action::StartRollCall(token)) => {
+                    remote.start_roll_call(token.clone().into()).await.map(drop)
+                },
+                Action::RollCall(ring_core::Action::MarkItself(token)) => {
+                    remote.mark_itself(token.clone().into()).await.map(drop)
+                },
+                Action::Elect(candidate) => remote.elect(candidate.clone()).await.map(drop),
+                Action::Elected(leader) => remote.elected(leader.clone()).await.map(drop),
+                // asks this node only
+                Action::StartElection => return Ok(()),
+            };
+            let err = match result {
+                Ok(()) => {
+                    self.failures = 0;
+                    self.events.send(Event {
+                        peer: address,
+                        ..self.events.event(Kind::Forwarded, action.describe())
+                    });
+                    return Ok(());
+                },
+                Err(err) => err,
+            };
+            self.events.send(Event {
+                peer: address.clone(),
+                error: err.to_string(),
+                ..self.events.event(Kind::PeerFailed, action.describe())
+            });
+            let is_last = {
+                let membership = self.membership.lock().unwrap();
+                !membership.is_member() || address == membership.me()
+            };
+            if is_last {
+                return Err(err);
+            }
+            warn!("Can't pass {:?} on to {}: {}", action, address, err);
+            if !self.failed(&address, &err) {
+                return Err(err);
+            }
+        }
+    }
+
+    /// Takes over the member list of the successor, so changes spread around
+    /// the ring, and joins again if this node isn't in it.
+    async fn check_successor(&mut self) {
+        if self.membership.lock().unwrap().has_left() {
+            return;
+        }
+        let (address, remote) = match self.remote().await {
+            Some(remote) => remote,
+            None => return,
+        };
+        let members = match remote.get_members().await {
+            Ok(members) => members,
+            Err(err) => {
+                self.events.send(Event {
+                    peer: address.clone(),
+                    error: err.to_string(),
+                    ..self.events.event(Kind::PeerFailed, ("PING", ""))
+                });
+                self.failed(&address, &err);
+                return;
+            },
+        };
+        self.failures = 0;
+        let (is_member, me) = {
+            let mut membership = self.membership.lock().unwrap();
+            if membership.adopt(members) {
+                debug!("Members: {:?}", membership.to_proto().members);
+            }
+            (membership.is_member(), membership.me().to_owned())
+        };
+        if is_member {
+            return;
+        }
+        // the successor has become the contact
+        if let Some((address, remote)) = self.remote().await {
+            match remote.join(me).await {
+                Ok(members) => {
+                    info!("Joined the ring through {}", address);
+                    self.membership.lock().unwrap().replace(members);
+                },
+                Err(e) => warn!("Can't join the ring through {}: {}", address, e),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RingTransport for Node {
+    async fn send(&mut self, action: &ring_core::Action) -> Result<(), TransportError> {
+        self.forward(&Action::RollCall(action.clone())).await
+            .map_err(|err| TransportError::new(&err.to_string()))
+    }
+}
+
+/// Whether this node is in the ring and doesn't know a leader that still is.
+fn needs_election(membership: &SharedMembership, leader: &SharedLeader) -> bool {
+    let membership = membership.lock().unwrap();
+    let leader = leader.lock().unwrap();
+    membership.is_member()
+        && !leader.as_ref().is_some_and(|leader| membership.contains(&leader.address))
+}
+
+/// Checks on the successor every `PING_INTERVAL`, apart from the worker so
+/// a slow successor doesn't hold actions up, and has the worker start an
+/// election while the ring has no leader.
+async fn health_loop(mut node: Node, sender: Sender<Action>, leader: SharedLeader) {
+    let membership = node.membership.clone();
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
     loop {
-        match receiver.recv().await {
-            Some(Action::StartRollCall) => {
-                if !in_roll_call {
-                    if remote.start_roll_call().await.is_ok() {
-                        debug!("ON");
-                        in_roll_call = true;
+        ping.tick().await;
+        node.check_successor().await;
+        if needs_election(&membership, &leader) {
+            // with a full queue the next check asks again
+            if let Err(TrySendError::Closed(_)) = sender.try_send(Action::StartElection) {
+                break;
+            }
+        }
+    }
+}
+
+/// Handles the actions reaching a node.
+///
+/// Roll calls go to the successor in the ring, which is `link` if it
+/// doesn't speak gRPC, like the JSON-RPC ring service. Elections and the
+/// membership need gRPC, they go around the members only.
+async fn worker_loop(
+    mut receiver: Receiver<Action>,
+    mut node: Node,
+    mut link: Option<Watched>,
+    last_roll_call: LastRollCall,
+    mut elector: Elector,
+    leader: SharedLeader,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let membership = node.membership.clone();
+    let events = node.events.clone();
+    let mut ring = ring_core::Ring::new(membership.lock().unwrap().me().to_owned());
+    loop {
+        let action = receiver.recv().await;
+        if let Some(action) = &action {
+            events.send(events.event(Kind::Received, action.describe()));
+        }
+        match action {
+            Some(Action::RollCall(action)) => {
+                if ring.skips(&action) {
+                    events.send(events.event(Kind::Skipped, events::describe(&action)));
+                }
+                let result = match &mut link {
+                    Some(next) => ring.handle(action, next).await,
+                    None => ring.handle(action, &mut node).await,
+                };
+                if let Some(result) = result {
+                    *last_roll_call.lock().unwrap() = Some(result.into());
+                }
+            },
+            Some(Action::Elect(candidate)) => {
+                let token = candidate.address.clone();
+                let vote = if candidate.address.is_empty() {
+                    // a client asks for an election
+                    if elector.is_running() {
+                        Vote::Drop
+                    } else {
+                        Vote::Pass(elector.start())
                     }
+                } else if !membership.lock().unwrap().contains(&candidate.address) {
+                    // a candidate that isn't in the ring anymore can't win
+                    elector.vote(Candidate::default())
                 } else {
-                    if remote.mark_itself().await.is_ok() {
-                        debug!("OFF");
-                        in_roll_call = false;
-                    }
+                    elector.vote(candidate)
+                };
+                let forwarded = match vote {
+                    Vote::Pass(candidate) => {
+                        debug!("ELECT {}", candidate.address);
+                        node.forward(&Action::Elect(candidate)).await
+                    },
+                    Vote::Won(me) => {
+                        info!("Elected as the leader");
+                        *leader.lock().unwrap() = Some(me.clone());
+                        node.forward(&Action::Elected(me)).await
+                    },
+                    Vote::Drop => {
+                        debug!("DROP");
+                        events.send(events.event(Kind::Skipped, ("ELECT", &token)));
+                        Ok(())
+                    },
+                };
+                if let Err(e) = forwarded {
+                    warn!("Can't pass the election on: {}", e);
                 }
             },
-            Some(Action::MarkItself) => {
-                if in_roll_call {
-                    if remote.mark_itself().await.is_ok() {
-                        debug!("OFF");
-                        in_roll_call = false;
+            Some(Action::Elected(elected)) => {
+                if elector.elected(&elected) {
+                    info!("{} is the leader", elected.address);
+                    *leader.lock().unwrap() = Some(elected.clone());
+                    if let Err(e) = node.forward(&Action::Elected(elected)).await {
+                        warn!("Can't announce the leader: {}", e);
                     }
                 } else {
-                    debug!("SKIP");
+                    events.send(events.event(Kind::Skipped, ("ELECTED", &elected.address)));
+                }
+            },
+            Some(Action::StartElection) => {
+                // the health check may ask again before the election ends
+                if !elector.is_running() && needs_election(&membership, &leader) {
+                    info!("Starting an election");
+                    let candidate = elector.start();
+                    if let Err(e) = node.forward(&Action::Elect(candidate)).await {
+                        warn!("Can't start the election: {}", e);
+                    }
                 }
             },
             None => break,
@@ -91,24 +525,99 @@ async fn worker_loop(mut receiver: Receiver<Action>)
     Ok(())
 }
 
+/// The link to a successor that isn't a member, which reports on the
+/// actions sent over it like `Node` does.
+struct Watched {
+    address: String,
+    next: Box<dyn RingTransport>,
+    events: Events,
+}
+
+#[async_trait]
+impl RingTransport for Watched {
+    async fn send(&mut self, action: &ring_core::Action) -> Result<(), TransportError> {
+        let result = self.next.send(action).await;
+        let event = match &result {
+            Ok(()) => self.events.event(Kind::Forwarded, events::describe(action)),
+            Err(err) => Event {
+                error: err.to_string(),
+                ..self.events.event(Kind::PeerFailed, events::describe(action))
+            },
+        };
+        self.events.send(Event {
+            peer: self.address.clone(),
+            ..event
+        });
+        result
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
     let (tx, rx) = mpsc::channel(4);
-    let addr = env::var("ADDRESS")?.parse()?;
-    let ring_service = RingService::new(tx);
+    let address = env::var("ADDRESS")?;
+    let addr = address.parse()?;
+    let config = LinkConfig::from_env()?;
+    let scheme = if config.tls.is_some() { "https" } else { "http" };
+    // the address other nodes reach this one at
+    let me = env::var("ADVERTISE").unwrap_or_else(|_| format!("{}://{}", scheme, address));
+    // without a node to join through this one starts the ring
+    let contact = env::var("NEXT").ok();
+    let events = Events::new(me.clone());
+    let link = match contact.as_deref() {
+        Some(next) if matches!(Peer::parse(next), Peer::JsonRpc(_)) => Some(Watched {
+            address: next.to_owned(),
+            next: grpc_ring::connect(next, &config).await?,
+            events: events.clone(),
+        }),
+        _ => None,
+    };
+    let id = match env::var("NODE_ID") {
+        Ok(id) => id.parse()?,
+        Err(_) => election::default_id(&me),
+    };
+    info!("Node id is {}", id);
+    let elector = Elector::new(id, me.clone(), ELECTION_TIMEOUT);
+    // a node linked to a successor that doesn't speak gRPC starts the ring
+    // of the members, others join it
+    let membership = match contact {
+        Some(_) if link.is_none() => Membership::joining(me.clone()),
+        _ => Membership::new(me.clone()),
+    };
+    let contact = contact.filter(|_| link.is_none());
+    let membership = Arc::new(Mutex::new(membership));
+    let last_roll_call = Arc::new(Mutex::new(None));
+    let leader = Arc::new(Mutex::new(None));
+    let ring_service = RingService::new(
+        tx.clone(),
+        membership.clone(),
+        last_roll_call.clone(),
+        leader.clone(),
+        config.clone(),
+        events.clone(),
+    );
     let svc = RingServer::new(ring_service);
+    let mut server = Server::builder();
+    if let Some(tls) = &config.tls {
+        info!("Serving TLS");
+        server = server.tls_config(tls.server()?)?;
+    }
+
+    let health = Node::new(membership.clone(), contact.clone(), config.clone(), events.clone());
+    tokio::spawn(health_loop(health, tx, leader.clone()));
 
     info!("Worker is running");
     tokio::spawn(async move {
-        worker_loop(rx).await.unwrap();
+        let node = Node::new(membership, contact, config, events);
+        worker_loop(rx, node, link, last_roll_call, elector, leader).await.unwrap()
     });
 
-    info!("Server is running"); 
-    Server::builder()
+    info!("Server is running");
+    server
         .add_service(svc)
         .serve(addr)
-        .await?; 
+        .await?;
 
     Ok(())
 }
@@ -0,0 +1,86 @@
+use std::{env, fmt, fs};
+use tonic::transport::{Certificate, ClientTlsConfig, Identity, ServerTlsConfig};
+use crate::error::RingGrpcError;
+
+/// TLS settings of a node or client, from PEM files named by the environment:
+///
+/// * `TLS_CA` - the CA that signs the certificates of the nodes,
+/// * `TLS_CERT` and `TLS_KEY` - the certificate to serve and to show to the
+///   nodes that ask for one,
+/// * `TLS_CLIENT_CA` - the CA the clients must have a certificate from, no
+///   client certificates are asked for without it,
+/// * `TLS_DOMAIN` - the name the certificates of the nodes must have,
+///   the host of their address by default.
+#[derive(Clone)]
+pub struct TlsConfig {
+    ca: Certificate,
+    identity: Option<Identity>,
+    client_ca: Option<Certificate>,
+    domain: Option<String>,
+}
+
+impl TlsConfig {
+    /// `None` if neither `TLS_CA` nor `TLS_CERT` is set, which means plaintext.
+    pub fn from_env() -> Result<Option<Self>, RingGrpcError> {
+        let ca = env::var("TLS_CA").ok();
+        let cert = env::var("TLS_CERT").ok();
+        if ca.is_none() && cert.is_none() {
+            return Ok(None);
+        }
+        let ca = ca.ok_or_else(|| RingGrpcError::new("TLS_CERT needs TLS_CA to check other nodes"))?;
+        let identity = match cert {
+            Some(cert) => {
+                let key = env::var("TLS_KEY")
+                    .map_err(|_| RingGrpcError::new("TLS_CERT needs TLS_KEY"))?;
+                Some(Identity::from_pem(read(&cert)?, read(&key)?))
+            },
+            None => None,
+        };
+        let client_ca = match env::var("TLS_CLIENT_CA") {
+            Ok(path) => Some(Certificate::from_pem(read(&path)?)),
+            Err(_) => None,
+        };
+        Ok(Some(Self {
+            ca: Certificate::from_pem(read(&ca)?),
+            identity,
+            client_ca,
+            domain: env::var("TLS_DOMAIN").ok(),
+        }))
+    }
+
+    pub fn server(&self) -> Result<ServerTlsConfig, RingGrpcError> {
+        let identity = self.identity.clone()
+            .ok_or_else(|| RingGrpcError::new("serving TLS needs TLS_CERT and TLS_KEY"))?;
+        let mut config = ServerTlsConfig::new().identity(identity);
+        if let Some(client_ca) = &self.client_ca {
+            config = config.client_ca_root(client_ca.clone());
+        }
+        Ok(config)
+    }
+
+    pub fn client(&self) -> ClientTlsConfig {
+        let mut config = ClientTlsConfig::new().ca_certificate(self.ca.clone());
+        if let Some(identity) = &self.identity {
+            config = config.identity(identity.clone());
+        }
+        if let Some(domain) = &self.domain {
+            config = config.domain_name(domain.clone());
+        }
+        config
+    }
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("identity", &self.identity.is_some())
+            .field("client_ca", &self.client_ca.is_some())
+            .field("domain", &self.domain)
+            .finish()
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, RingGrpcError> {
+    fs::read(path)
+        .map_err(|err| RingGrpcError::new(&format!("can't read {}: {}", path, err)))
+}
@@ -5,21 +5,37 @@ THIRD=127.0.0.1:6666
 start_service() {
     NEXT_ADDR="http://$2"
     echo "start server $1"
-    RUST_LOG=grpc_ring=trace RUST_BACKTRACE=1 ADDRESS=$1 NEXT=$NEXT_ADDR target/debug/grpc-ring > $3 2>&1 &
+    RUST_LOG=grpc_ring=trace,ring_core=debug RUST_BACKTRACE=1 ADDRESS=$1 NEXT=$NEXT_ADDR target/debug/grpc-ring > $3 2>&1 &
+    LAST_PID=$!
 }
 
 cargo build
 
 start_service $FIRST $SECOND first.log
 start_service $SECOND $THIRD second.log
+SECOND_PID=$LAST_PID
 start_service $THIRD $FIRST third.log
 
+sleep 3
+
+NEXT="http://$FIRST" target/debug/grpc-ring-client members
+NEXT="http://$FIRST" target/debug/grpc-ring-client leader
+NEXT="http://$FIRST" target/debug/grpc-ring-client
+
 sleep 1
 
+NEXT="http://$FIRST" target/debug/grpc-ring-client last-roll-call
+
+echo "stop server $SECOND"
+kill $SECOND_PID
+
 NEXT="http://$FIRST" target/debug/grpc-ring-client
 
 sleep 1
 
+NEXT="http://$FIRST" target/debug/grpc-ring-client members
+NEXT="http://$FIRST" target/debug/grpc-ring-client leader
+
 pkill grpc-ring
 
 echo FIRST
@@ -1,132 +1,216 @@
+//! A ring node serving JSON-RPC, configured by environment variables:
+//!
+//! - `ADDRESS`: the address to listen on.
+//! - `NEXT`: the next node, `host:port` for JSON-RPC or a `http(s)://` gRPC one.
+//! - `QUEUE`: how many actions wait for the worker, 64 by default.
+//! - `NOTIFY`: when set, actions go to a JSON-RPC next node as notifications.
+//! - `TLS_*` and `RETRY_*`: the link to a gRPC next node, as for `grpc-ring`.
+
+use async_trait::async_trait;
 use failure::Error;
-use jsonrpc::{self, Client};
-use jsonrpc::error::Error as ClientError;
-use jsonrpc::simple_http::Builder;
+use grpc_ring::LinkConfig;
 use jsonrpc_http_server::ServerBuilder;
-use jsonrpc_http_server::jsonrpc_core::{IoHandler, Error as ServerError, Value};
-use log::{debug, error, trace};
-use serde::Deserialize;
-use std::{env, fmt, net::SocketAddr, sync::Mutex, thread};
-use std::sync::mpsc::{channel, Sender};
+use jsonrpc_http_server::jsonrpc_core::futures::future::{Either, Future, FutureExt};
+use jsonrpc_http_server::jsonrpc_core::serde_json::{self, json};
+use jsonrpc_http_server::jsonrpc_core::{
+    BoxFuture, Call, Error as ServerError, Id, MetaIoHandler, MethodCall, Middleware, Output,
+    Params, Response, Value,
+};
+use log::{error, trace};
+use ring_core::json_rpc::{self, JsonRpcTransport, START_ROLL_CALL, MARK_ITSELF, STATUS};
+use ring_core::roll_call::now;
+use ring_core::{Action, Peer, Ring, RingTransport, RollCall, RollCallResult, TransportError};
+use std::{env, fmt, net::SocketAddr, sync::{Arc, Mutex}};
+use tokio::runtime;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+/// Actions waiting for the worker, calls wait for room when it's full.
+const QUEUE: usize = 64;
+
+
+/// What the `status` method reports, kept up to date by the worker.
+#[derive(Default)]
+struct Status {
+    in_roll_call: bool,
+    started: u64,
+    last_roll_call: Option<RollCallResult>,
+    /// Whether the last action passed on reached the next node, and when.
+    reachable: Option<bool>,
+    checked: u64,
+    error: Option<String>,
+}
 
 
-const START_ROLL_CALL: &str = "start_roll_call";
-const MARK_ITSELF: &str = "mark_itself";
+/// The link to the next node, noting in the status whether it works.
+struct Checked {
+    transport: Box<dyn RingTransport>,
+    status: Arc<Mutex<Status>>,
+}
 
+#[async_trait]
+impl RingTransport for Checked {
+    async fn send(&mut self, action: &Action) -> Result<(), TransportError> {
+        let result = self.transport.send(action).await;
+        let mut status = self.status.lock().unwrap();
+        status.reachable = Some(result.is_ok());
+        status.checked = now();
+        status.error = result.as_ref().err().map(ToString::to_string);
+        result
+    }
 
-struct Remote {
-    client: Client,
+    async fn send_all(&mut self, actions: &[Action]) -> Vec<Result<(), TransportError>> {
+        let results = self.transport.send_all(actions).await;
+        if let Some(result) = results.last() {
+            let mut status = self.status.lock().unwrap();
+            status.reachable = Some(result.is_ok());
+            status.checked = now();
+            status.error = result.as_ref().err().map(ToString::to_string);
+        }
+        results
+    }
 }
 
 
-impl Remote {
-    fn new(addr: SocketAddr) -> Self {
-        let url = format!("http://{}", addr);
-        let builder = Builder::new();
-        let t = builder.url(&url).unwrap()
-            .build();
-        let client = Client::with_transport(t);
-        Self { client }
+/// Passes on the actions queued since it last woke up in one go, a batch
+/// request when the next node speaks JSON-RPC.
+async fn worker(mut rx: Receiver<Action>, me: String, mut next: Checked) {
+    let mut ring = Ring::new(me);
+    while let Some(action) = rx.recv().await {
+        let mut actions = vec![action];
+        while let Ok(action) = rx.try_recv() {
+            actions.push(action);
+        }
+        let results = ring.handle_all(actions, &mut next).await;
+        let mut status = next.status.lock().unwrap();
+        status.in_roll_call = ring.in_roll_call();
+        status.started = ring.started();
+        if let Some(result) = results.into_iter().last() {
+            status.last_roll_call = Some(result);
+        }
     }
+}
 
-    fn call_method<T>(&self, meth: &str, args: &[Value])
-    -> Result<T, ClientError>
-    where 
-        T: for <'de> Deserialize<'de>
-    {
-        let binding = [jsonrpc::arg(args)];
-        let request = self.client.build_request(meth, &binding);
-        self.client.send_request(request).and_then(|res| 
-            res.result::<T>()
-        )
-    }
 
-    fn start_roll_call(&self) -> Result<bool, ClientError> {
-        self.call_method(START_ROLL_CALL, &[])
+/// Links to the next node, which may speak JSON-RPC or gRPC. With `NOTIFY`
+/// set actions go to a JSON-RPC node as notifications.
+async fn link(next: &str) -> Result<Box<dyn RingTransport>, Error> {
+    match Peer::parse(next) {
+        Peer::JsonRpc(addr) if env::var("NOTIFY").is_ok() => {
+            Ok(Box::new(JsonRpcTransport::new(&addr)?.with_notifications()))
+        },
+        _ => Ok(grpc_ring::connect(next, &LinkConfig::from_env()?).await?),
     }
+}
 
-    fn mark_itself(&self) -> Result<bool, ClientError> {
-        self.call_method(MARK_ITSELF, &[])
-    }
 
+/// Runs notifications of the roll call methods as calls and drops the
+/// answer: the handler only runs notifications of methods registered as
+/// such, and a script shouldn't wait for a reply to pass a token on.
+#[derive(Default)]
+struct Notifications;
+
+impl Middleware<()> for Notifications {
+    type Future = BoxFuture<Option<Response>>;
+    type CallFuture = BoxFuture<Option<Output>>;
+
+    fn on_call<F, X>(&self, call: Call, meta: (), next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, ()) -> X + Send + Sync,
+        X: Future<Output = Option<Output>> + Send + 'static,
+    {
+        match call {
+            Call::Notification(notification)
+                if [START_ROLL_CALL, MARK_ITSELF].contains(&notification.method.as_str()) =>
+            {
+                trace!("{} notification", notification.method);
+                let call = Call::MethodCall(MethodCall {
+                    jsonrpc: notification.jsonrpc,
+                    method: notification.method,
+                    params: notification.params,
+                    id: Id::Null,
+                });
+                Either::Left(next(call, meta).map(|_| None).boxed())
+            },
+            call => Either::Right(next(call, meta)),
+        }
+    }
 }
 
 
-enum Action {
-    StartRollCall,
-    MarkItself,
+fn to_internal<E: fmt::Display>(err: E) -> ServerError {
+    error!("Error {}", err);
+    ServerError::internal_error()
 }
 
 
-fn spawn_worker() -> Result<Sender<Action>, Error> {
-    let (tx, rx) = channel();
-    let next: SocketAddr = env::var("NEXT")?.parse()?;
-    thread::spawn(move || {
-        let remote = Remote::new(next);
-        let mut in_roll_call = false;
-        for action in rx.iter() {
-            match action {
-                Action::StartRollCall => {
-                    if !in_roll_call {
-                        if remote.start_roll_call().is_ok() {
-                            debug!("ON");
-                            in_roll_call = true;
-                        }
-                    } else {
-                        if remote.mark_itself().is_ok() {
-                            debug!("OFF");
-                            in_roll_call = false;
-                        }
-                    }
-                },
-                Action::MarkItself => {
-                    if in_roll_call {
-                        if remote.mark_itself().is_ok() {
-                            debug!("OFF");
-                            in_roll_call = false;
-                        }
-                    } else {
-                        debug!("SKIP");
-                    }
-                },
-            }
+fn add_action<F>(io: &mut MetaIoHandler<(), Notifications>, method: &str, tx: &Sender<Action>, action: F)
+where
+    F: Fn(RollCall) -> Action + Send + Sync + 'static,
+{
+    let tx = tx.clone();
+    let name = method.to_uppercase();
+    io.add_method(method, move |params: Params| {
+        trace!("{}", name);
+        let tx = tx.clone();
+        let action = action(json_rpc::token(params.into()));
+        async move {
+            // waits while the worker is behind
+            tx.send(action).await
+                .map_err(to_internal)
+                .map(|_| Value::Bool(true))
         }
     });
-    Ok(tx)
 }
 
 
-fn to_internal<E: fmt::Display>(err: E) -> ServerError {
-    error!("Error {}", err);
-    ServerError::internal_error()
+fn add_status(io: &mut MetaIoHandler<(), Notifications>, me: String, next: String, tx: &Sender<Action>, status: Arc<Mutex<Status>>) {
+    let tx = tx.clone();
+    io.add_sync_method(STATUS, move |_| {
+        let status = status.lock().unwrap();
+        let last_roll_call = serde_json::to_value(&status.last_roll_call)
+            .map_err(to_internal)?;
+        Ok(json!({
+            "node": me,
+            "in_roll_call": status.in_roll_call,
+            "roll_calls_started": status.started,
+            "last_roll_call": last_roll_call,
+            "queued": tx.max_capacity() - tx.capacity(),
+            "queue_size": tx.max_capacity(),
+            "next": {
+                "address": next,
+                "reachable": status.reachable,
+                "checked": status.checked,
+                "error": status.error,
+            },
+        }))
+    });
 }
 
 
 fn main() -> Result<(), Error> {
     env_logger::init();
-    let tx = spawn_worker()?;
     let addr: SocketAddr = env::var("ADDRESS")?.parse()?;
-    let mut io = IoHandler::default();
-    let sender = Mutex::new(tx.clone());
-    io.add_sync_method(START_ROLL_CALL, move |_| {
-        trace!("START_ROLL_CALL");
-        let tx = sender
-            .lock()
-            .map_err(to_internal)?;
-        tx.send(Action::StartRollCall)
-            .map_err(to_internal)
-            .map(|_| Value::Bool(true))
-    });
-    let sender = Mutex::new(tx.clone());
-    io.add_sync_method(MARK_ITSELF, move |_| {
-        trace!("MARK_ITSELF");
-        let tx = sender
-            .lock()
-            .map_err(to_internal)?;
-        tx.send(Action::MarkItself)
-            .map_err(to_internal)
-            .map(|_| Value::Bool(true))
-    });
+    let me = format!("jsonrpc://{}", addr);
+    let next = env::var("NEXT")?;
+    let queue = match env::var("QUEUE") {
+        Ok(queue) => queue.parse::<usize>()?.max(1),
+        Err(_) => QUEUE,
+    };
+    let runtime = runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let status = Arc::new(Mutex::new(Status::default()));
+    let next_node = Checked {
+        transport: runtime.block_on(link(&next))?,
+        status: status.clone(),
+    };
+    let (tx, rx) = mpsc::channel(queue);
+    runtime.spawn(worker(rx, me.clone(), next_node));
+    let mut io = MetaIoHandler::with_middleware(Notifications);
+    add_action(&mut io, START_ROLL_CALL, &tx, Action::StartRollCall);
+    add_action(&mut io, MARK_ITSELF, &tx, Action::MarkItself);
+    add_status(&mut io, me, next, &tx, status);
     let server = ServerBuilder::new(io).start_http(&addr)?;
-    Ok(server.wait())
+    server.wait();
+    Ok(())
 }
@@ -0,0 +1,55 @@
+FIRST=127.0.0.1:4444
+SECOND=127.0.0.1:5555
+THIRD=127.0.0.1:6666
+
+start_service() {
+    echo "start server $1"
+    RUST_LOG=json_rpc_ring=trace,ring_core=debug RUST_BACKTRACE=1 ADDRESS=$1 NEXT=$2 target/debug/json-rpc-ring > $3 2>&1 &
+    LAST_PID=$!
+}
+
+call() {
+    curl -s -H 'Content-Type: application/json' -d "$2" "http://$1"
+    echo
+}
+
+cargo build
+
+start_service $FIRST $SECOND first.log
+NOTIFY=1 start_service $SECOND $THIRD second.log
+SECOND_PID=$LAST_PID
+start_service $THIRD $FIRST third.log
+
+sleep 1
+
+# a roll call, the status of the node and a call of a missing method in one batch
+call $FIRST '[
+    {"jsonrpc": "2.0", "id": 1, "method": "start_roll_call", "params": []},
+    {"jsonrpc": "2.0", "id": 2, "method": "status"},
+    {"jsonrpc": "2.0", "id": 3, "method": "missing"}
+]'
+
+sleep 1
+
+call $FIRST '{"jsonrpc": "2.0", "id": 4, "method": "status"}'
+
+echo "stop server $SECOND"
+kill $SECOND_PID
+
+# a notification gets no reply
+call $FIRST '{"jsonrpc": "2.0", "method": "start_roll_call", "params": []}'
+
+sleep 1
+
+call $FIRST '{"jsonrpc": "2.0", "id": 5, "method": "status"}'
+
+pkill json-rpc-ring
+
+echo FIRST
+cat first.log
+echo SECOND
+cat second.log
+echo THIRD
+cat third.log
+
+rm first.log second.log third.log
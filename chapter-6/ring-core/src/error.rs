@@ -0,0 +1,22 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub struct TransportError {
+    message: String,
+}
+
+impl TransportError {
+    pub fn new(msg: &str) -> Self {
+        TransportError {
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TransportError {}
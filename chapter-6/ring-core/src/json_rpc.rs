@@ -0,0 +1,151 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use hyper::{Body, Client, Request, Uri};
+use hyper::client::HttpConnector;
+use hyper::header::CONTENT_TYPE;
+use serde_json::{json, Value};
+use crate::{Action, RingTransport, RollCall, TransportError};
+
+pub const START_ROLL_CALL: &str = "start_roll_call";
+pub const MARK_ITSELF: &str = "mark_itself";
+pub const STATUS: &str = "status";
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The token in the params of a call, older nodes send none.
+pub fn token(params: Value) -> RollCall {
+    match params {
+        Value::Array(params) => params.into_iter()
+            .next()
+            .and_then(|token| serde_json::from_value(token).ok())
+            .unwrap_or_default(),
+        _ => RollCall::default(),
+    }
+}
+
+/// Link to a node serving the ring over JSON-RPC.
+///
+/// Actions go as calls, or as notifications with `with_notifications`:
+/// those get no reply, so an error of the node goes unnoticed, and nodes
+/// older than the async worker ignore them. Actions passed on together with
+/// `send_all` go in one batch request.
+pub struct JsonRpcTransport {
+    client: Client<HttpConnector>,
+    uri: Uri,
+    id: u64,
+    notify: bool,
+}
+
+impl JsonRpcTransport {
+    pub fn new(addr: &str) -> Result<Self, TransportError> {
+        let uri = format!("http://{}", addr).parse()
+            .map_err(|_| TransportError::new(&format!("bad JSON-RPC address {}", addr)))?;
+        Ok(Self {
+            client: Client::new(),
+            uri,
+            id: 0,
+            notify: false,
+        })
+    }
+
+    pub fn with_notifications(mut self) -> Self {
+        self.notify = true;
+        self
+    }
+
+    /// The call, or notification, passing an action on.
+    fn request(&mut self, action: &Action) -> Value {
+        let (method, token) = match action {
+            Action::StartRollCall(token) => (START_ROLL_CALL, token),
+            Action::MarkItself(token) => (MARK_ITSELF, token),
+        };
+        let mut body = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": [token],
+        });
+        if !self.notify {
+            self.id += 1;
+            body["id"] = json!(self.id);
+        }
+        body
+    }
+
+    /// Posts a call or a batch, returns the reply unless all were notifications.
+    async fn post(&mut self, body: Value) -> Result<Option<Value>, TransportError> {
+        let request = Request::post(self.uri.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .map_err(to_transport)?;
+        let response = tokio::time::timeout(TIMEOUT, self.client.request(request)).await
+            .map_err(to_transport)?
+            .map_err(to_transport)?;
+        if !response.status().is_success() {
+            return Err(TransportError::new(&format!("HTTP {}", response.status())));
+        }
+        let bytes = hyper::body::to_bytes(response.into_body()).await
+            .map_err(to_transport)?;
+        if self.notify {
+            return Ok(None);
+        }
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(to_transport)
+    }
+}
+
+#[async_trait]
+impl RingTransport for JsonRpcTransport {
+    async fn send(&mut self, action: &Action) -> Result<(), TransportError> {
+        let body = self.request(action);
+        match self.post(body).await? {
+            Some(reply) => outcome(&reply),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends the actions as one batch, its replies are matched by id.
+    async fn send_all(&mut self, actions: &[Action]) -> Vec<Result<(), TransportError>> {
+        if actions.len() < 2 {
+            let mut results = Vec::new();
+            for action in actions {
+                results.push(self.send(action).await);
+            }
+            return results;
+        }
+        let calls: Vec<Value> = actions.iter().map(|action| self.request(action)).collect();
+        let ids: Vec<Value> = calls.iter().map(|call| call["id"].clone()).collect();
+        match self.post(Value::Array(calls)).await {
+            Ok(None) => actions.iter().map(|_| Ok(())).collect(),
+            Ok(Some(Value::Array(replies))) => ids.iter()
+                .map(|id| match replies.iter().find(|reply| &reply["id"] == id) {
+                    Some(reply) => outcome(reply),
+                    None => Err(TransportError::new(&format!("no reply to call {}", id))),
+                })
+                .collect(),
+            // a single error object answers a batch the node couldn't read
+            Ok(Some(reply)) => {
+                let err = outcome(&reply).err()
+                    .map(|err| err.to_string())
+                    .unwrap_or_else(|| "bad reply to a batch".to_owned());
+                actions.iter().map(|_| Err(TransportError::new(&err))).collect()
+            },
+            Err(err) => {
+                let err = err.to_string();
+                actions.iter().map(|_| Err(TransportError::new(&err))).collect()
+            },
+        }
+    }
+}
+
+/// Whether the reply to a call reports an error.
+fn outcome(reply: &Value) -> Result<(), TransportError> {
+    match reply.get("error") {
+        Some(error) => Err(TransportError::new(&error.to_string())),
+        None => Ok(()),
+    }
+}
+
+fn to_transport<E: std::fmt::Display>(err: E) -> TransportError {
+    TransportError::new(&err.to_string())
+}
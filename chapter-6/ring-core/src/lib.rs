@@ -0,0 +1,272 @@
+//! The roll call of the ring services, apart from the protocol the nodes
+//! speak: every service hands the actions that reach a node to a `Ring` and
+//! the `RingTransport` to its successor passes them on.
+mod error;
+#[cfg(feature = "json-rpc")]
+pub mod json_rpc;
+pub mod memory;
+pub mod roll_call;
+
+use async_trait::async_trait;
+use log::{debug, info, warn};
+
+pub use crate::error::TransportError;
+pub use crate::roll_call::{Hop, Mark, RollCall, RollCallResult};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    StartRollCall(RollCall),
+    MarkItself(RollCall),
+}
+
+/// The link of a node to its successor.
+#[async_trait]
+pub trait RingTransport: Send {
+    async fn send(&mut self, action: &Action) -> Result<(), TransportError>;
+
+    /// Sends actions in order, with a result for each of them. Transports
+    /// that can send them at once, like JSON-RPC batches, override it.
+    async fn send_all(&mut self, actions: &[Action]) -> Vec<Result<(), TransportError>> {
+        let mut results = Vec::with_capacity(actions.len());
+        for action in actions {
+            results.push(self.send(action).await);
+        }
+        results
+    }
+}
+
+/// The address of a node and the protocol it speaks.
+///
+/// Addresses with a scheme are gRPC endpoints, except for `jsonrpc://`,
+/// bare `host:port` ones are JSON-RPC servers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Peer {
+    Grpc(String),
+    JsonRpc(String),
+}
+
+impl Peer {
+    pub fn parse(address: &str) -> Self {
+        if let Some(addr) = address.strip_prefix("jsonrpc://") {
+            Peer::JsonRpc(addr.to_owned())
+        } else if address.contains("://") {
+            Peer::Grpc(address.to_owned())
+        } else {
+            Peer::JsonRpc(address.to_owned())
+        }
+    }
+}
+
+/// The roll call state of a node.
+///
+/// A roll call starts at the node a client asks, goes around the ring once
+/// to switch every node on and a second time to switch them off again.
+#[derive(Debug)]
+pub struct Ring {
+    me: String,
+    in_roll_call: bool,
+    started: u64,
+}
+
+impl Ring {
+    pub fn new(me: String) -> Self {
+        Self {
+            me,
+            in_roll_call: false,
+            started: 0,
+        }
+    }
+
+    /// Whether this node switched on for a roll call going around.
+    pub fn in_roll_call(&self) -> bool {
+        self.in_roll_call
+    }
+
+    /// Roll calls this node started.
+    pub fn started(&self) -> u64 {
+        self.started
+    }
+
+    /// Whether an action that reaches this node now goes no further: a mark
+    /// of a roll call this node isn't part of.
+    pub fn skips(&self, action: &Action) -> bool {
+        matches!(action, Action::MarkItself(_)) && !self.in_roll_call
+    }
+
+    /// Passes on an action that reached this node, returns the result of a
+    /// roll call this node started and that came back to it.
+    pub async fn handle<T>(&mut self, action: Action, transport: &mut T)
+        -> Option<RollCallResult>
+    where
+        T: RingTransport + ?Sized,
+    {
+        if self.skips(&action) {
+            debug!("SKIP");
+            return None;
+        }
+        let (forward, result) = self.pass(action);
+        let sent = transport.send(&forward).await;
+        self.sent(&forward, sent);
+        result
+    }
+
+    /// Passes on actions that reached this node with a single `send_all`,
+    /// returns the results of roll calls this node started that came back.
+    ///
+    /// Every action is passed on as if the ones before it got through. If
+    /// some don't, the node ends up switched by the last one that did.
+    pub async fn handle_all<T>(&mut self, actions: Vec<Action>, transport: &mut T)
+        -> Vec<RollCallResult>
+    where
+        T: RingTransport + ?Sized,
+    {
+        let in_roll_call = self.in_roll_call;
+        let mut forwards = Vec::with_capacity(actions.len());
+        let mut results = Vec::new();
+        for action in actions {
+            if self.skips(&action) {
+                debug!("SKIP");
+                continue;
+            }
+            let (forward, result) = self.pass(action);
+            self.in_roll_call = matches!(forward, Action::StartRollCall(_));
+            forwards.push(forward);
+            results.extend(result);
+        }
+        self.in_roll_call = in_roll_call;
+        let sent = transport.send_all(&forwards).await;
+        for (forward, sent) in forwards.iter().zip(sent) {
+            self.sent(forward, sent);
+        }
+        results
+    }
+
+    /// The action to pass on for one that reached this node.
+    fn pass(&mut self, action: Action) -> (Action, Option<RollCallResult>) {
+        match action {
+            Action::StartRollCall(mut token) => {
+                if !self.in_roll_call {
+                    if token.id.is_empty() {
+                        token = roll_call::start(&self.me, self.started);
+                        self.started += 1;
+                    } else {
+                        roll_call::mark(&mut token, &self.me);
+                    }
+                    (Action::StartRollCall(token), None)
+                } else if token.initiator == self.me {
+                    info!("Roll call {} passed {} nodes", token.id, token.marks.len());
+                    let result = roll_call::finish(token.clone());
+                    (Action::MarkItself(token), Some(result))
+                } else {
+                    (Action::MarkItself(token), None)
+                }
+            },
+            Action::MarkItself(token) => (Action::MarkItself(token), None),
+        }
+    }
+
+    /// Switches this node by an action it passed on, if that got through.
+    fn sent(&mut self, forward: &Action, sent: Result<(), TransportError>) {
+        match sent {
+            Ok(()) => {
+                self.in_roll_call = matches!(forward, Action::StartRollCall(_));
+                debug!("{}", if self.in_roll_call { "ON" } else { "OFF" });
+            },
+            Err(e) => warn!("Can't pass the roll call on: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::memory::{Network, MemoryTransport};
+    use super::{Action, Ring, RollCall, RollCallResult};
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    struct Node {
+        ring: Ring,
+        receiver: UnboundedReceiver<Action>,
+        transport: MemoryTransport,
+    }
+
+    fn ring(network: &Network, addresses: &[&str]) -> Vec<Node> {
+        addresses.iter()
+            .enumerate()
+            .map(|(i, address)| Node {
+                ring: Ring::new(address.to_string()),
+                receiver: network.add(address),
+                transport: network.transport(addresses[(i + 1) % addresses.len()]),
+            })
+            .collect()
+    }
+
+    /// Handles actions until none are left, returns the finished roll calls.
+    async fn settle(nodes: &mut [Node]) -> Vec<RollCallResult> {
+        let mut results = Vec::new();
+        let mut idle = false;
+        while !idle {
+            idle = true;
+            for node in nodes.iter_mut() {
+                while let Ok(action) = node.receiver.try_recv() {
+                    idle = false;
+                    results.extend(node.ring.handle(action, &mut node.transport).await);
+                }
+            }
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn roll_call_goes_around() {
+        let network = Network::new();
+        let mut nodes = ring(&network, &["a", "b", "c"]);
+        for _ in 0..2 {
+            network.send("a", Action::StartRollCall(RollCall::default())).unwrap();
+            let results = settle(&mut nodes).await;
+            assert_eq!(results.len(), 1);
+            let result = &results[0];
+            assert_eq!(result.roll_call.initiator, "a");
+            let hops: Vec<_> = result.hops.iter()
+                .map(|hop| (hop.from.as_str(), hop.to.as_str()))
+                .collect();
+            assert_eq!(hops, [("a", "b"), ("b", "c"), ("c", "a")]);
+        }
+    }
+
+    #[tokio::test]
+    async fn roll_call_goes_around_in_batches() {
+        let network = Network::new();
+        let mut nodes = ring(&network, &["a", "b", "c"]);
+        network.send("a", Action::StartRollCall(RollCall::default())).unwrap();
+        let mut results = Vec::new();
+        while results.is_empty() {
+            for node in nodes.iter_mut() {
+                let mut actions = Vec::new();
+                while let Ok(action) = node.receiver.try_recv() {
+                    actions.push(action);
+                }
+                results.extend(node.ring.handle_all(actions, &mut node.transport).await);
+            }
+        }
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].hops.len(), 3);
+        // the second lap switches every node off again
+        settle(&mut nodes).await;
+        assert!(nodes.iter().all(|node| !node.ring.in_roll_call()));
+    }
+
+    #[tokio::test]
+    async fn roll_call_stops_at_missing_node() {
+        let network = Network::new();
+        let mut nodes = ring(&network, &["a", "b", "c"]);
+        network.remove("c");
+        network.send("a", Action::StartRollCall(RollCall::default())).unwrap();
+        assert!(settle(&mut nodes).await.is_empty());
+        // `a` switched on but `b` didn't and skips the mark switching `a` off
+        network.send("a", Action::MarkItself(RollCall::default())).unwrap();
+        assert!(settle(&mut nodes).await.is_empty());
+        nodes[2].receiver = network.add("c");
+        network.send("a", Action::StartRollCall(RollCall::default())).unwrap();
+        assert_eq!(settle(&mut nodes).await.len(), 1);
+    }
+}
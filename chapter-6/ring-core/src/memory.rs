@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use crate::{Action, RingTransport, TransportError};
+
+/// Nodes of one process, linked by channels instead of sockets.
+#[derive(Clone, Debug, Default)]
+pub struct Network {
+    nodes: Arc<Mutex<HashMap<String, UnboundedSender<Action>>>>,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node, the actions sent to it come out of the receiver.
+    pub fn add(&self, address: &str) -> UnboundedReceiver<Action> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.nodes.lock().unwrap().insert(address.to_owned(), tx);
+        rx
+    }
+
+    /// Takes a node off the network, as if it had stopped.
+    pub fn remove(&self, address: &str) {
+        self.nodes.lock().unwrap().remove(address);
+    }
+
+    pub fn send(&self, address: &str, action: Action) -> Result<(), TransportError> {
+        self.nodes.lock().unwrap()
+            .get(address)
+            .ok_or_else(|| TransportError::new(&format!("no node at {}", address)))?
+            .send(action)
+            .map_err(|_| TransportError::new(&format!("node at {} has stopped", address)))
+    }
+
+    pub fn transport(&self, next: &str) -> MemoryTransport {
+        MemoryTransport {
+            network: self.clone(),
+            next: next.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryTransport {
+    network: Network,
+    next: String,
+}
+
+#[async_trait]
+impl RingTransport for MemoryTransport {
+    async fn send(&mut self, action: &Action) -> Result<(), TransportError> {
+        self.network.send(&self.next, action.clone())
+    }
+}
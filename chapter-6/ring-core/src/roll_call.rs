@@ -0,0 +1,92 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "json-rpc")]
+use serde::{Deserialize, Serialize};
+
+/// A node the roll call token passed, with the time it got there.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "json-rpc", derive(Serialize, Deserialize), serde(default))]
+pub struct Mark {
+    pub node: String,
+    pub timestamp: u64,
+}
+
+/// The token of a roll call, sent around the ring and marked by every node.
+/// Clients start a roll call with an empty token.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "json-rpc", derive(Serialize, Deserialize), serde(default))]
+pub struct RollCall {
+    pub id: String,
+    pub initiator: String,
+    pub marks: Vec<Mark>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-rpc", derive(Serialize, Deserialize))]
+pub struct Hop {
+    pub from: String,
+    pub to: String,
+    pub latency: u64,
+}
+
+/// A roll call that went around the ring, with the latency of every hop,
+/// the last one back to the initiator.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-rpc", derive(Serialize, Deserialize))]
+pub struct RollCallResult {
+    pub roll_call: RollCall,
+    pub hops: Vec<Hop>,
+}
+
+/// Milliseconds since the Unix epoch, the unit of the marks.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Turns the empty token a client sent into a roll call started by `me`.
+///
+/// `seq` tells apart roll calls started by the same node in the same
+/// millisecond.
+pub fn start(me: &str, seq: u64) -> RollCall {
+    let started = now();
+    let mut token = RollCall {
+        id: format!("{}-{}", started, seq),
+        initiator: me.to_owned(),
+        marks: Vec::new(),
+    };
+    mark(&mut token, me);
+    token
+}
+
+/// Adds `me` to the nodes the token passed.
+pub fn mark(token: &mut RollCall, me: &str) {
+    token.marks.push(Mark {
+        node: me.to_owned(),
+        timestamp: now(),
+    });
+}
+
+/// The result of a token that got back to its initiator just now.
+///
+/// The clocks of the nodes are taken to be in sync, a hop measured as
+/// negative because they aren't counts as zero.
+pub fn finish(token: RollCall) -> RollCallResult {
+    let back = Mark {
+        node: token.initiator.clone(),
+        timestamp: now(),
+    };
+    let hops = token.marks.iter()
+        .zip(token.marks.iter().skip(1).chain(Some(&back)))
+        .map(|(from, to)| Hop {
+            from: from.node.clone(),
+            to: to.node.clone(),
+            latency: to.timestamp.saturating_sub(from.timestamp),
+        })
+        .collect();
+    RollCallResult {
+        roll_call: token,
+        hops,
+    }
+}